@@ -0,0 +1,95 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    check_credentials();
+    check_no_dotenv_macros();
+    write_build_info();
+}
+
+/// Production builds are expected to ship with credentials in their environment, so warn early
+/// rather than letting the exporter fail on startup.
+fn check_credentials() {
+    if env::var_os("CARGO_FEATURE_PRODUCTION").is_none() {
+        return;
+    }
+
+    for var in &["TESLA_ACCESS_TOKEN", "TESLA_REFRESH_TOKEN"] {
+        println!("cargo:rerun-if-env-changed={}", var);
+        if env::var_os(var).is_none() {
+            println!("cargo:warning={} is not set, the exporter will fail to authenticate unless it is provided at runtime", var);
+        }
+    }
+}
+
+/// Credentials are read at runtime via `env::var()`. A `dotenv!()` macro would bake them into the
+/// binary at compile time, so refuse to build if one sneaks back in.
+fn check_no_dotenv_macros() {
+    println!("cargo:rerun-if-changed=src");
+
+    let mut offenders = Vec::new();
+    for file in rust_sources(Path::new("src")) {
+        let source = fs::read_to_string(&file).unwrap_or_default();
+        for (index, line) in source.lines().enumerate() {
+            if line.contains("dotenv!(") {
+                offenders.push(format!("{}:{}", file.display(), index + 1));
+            }
+        }
+    }
+
+    if !offenders.is_empty() {
+        panic!(
+            "dotenv!() embeds secrets at compile time, use env::var() instead. Found in: {}",
+            offenders.join(", ")
+        );
+    }
+}
+
+fn rust_sources(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                files.extend(rust_sources(&path));
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+fn write_build_info() {
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let build_timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    println!("cargo:rustc-env=GIT_HASH={}", git_hash);
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", build_timestamp);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+
+    let build_info = Path::new(&env::var("OUT_DIR").unwrap()).join("build_info.rs");
+    fs::write(
+        build_info,
+        r#"pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_HASH: &str = env!("GIT_HASH");
+pub const BUILD_TIMESTAMP: &str = env!("BUILD_TIMESTAMP");
+"#,
+    )
+    .expect("Failed to write build_info.rs");
+}
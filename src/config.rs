@@ -0,0 +1,262 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Configuration file passed with `--config`, an alternative to environment variables.
+///
+/// Every setting maps to the environment variable of the same name, e.g. `[output] statsd_host`
+/// to `STATSD_HOST`. Environment variables take precedence over the file.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub auth: AuthSection,
+    #[serde(default)]
+    pub poller: PollerSection,
+    #[serde(default)]
+    pub metrics: MetricsSection,
+    #[serde(default)]
+    pub output: OutputSection,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AuthSection {
+    pub access_token: Option<String>,
+    pub refresh_token: Option<String>,
+    pub cert_pin_sha256: Option<String>,
+    pub api_url: Option<String>,
+    pub auth_url: Option<String>,
+    pub token_file: Option<String>,
+    pub token_reload_interval_secs: Option<u64>,
+    /// `[[auth.accounts]]` tables, one per account, mapped to `TESLA_ACCOUNTS`.
+    pub accounts: Option<Vec<AccountSection>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AccountSection {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PollerSection {
+    pub retry_on_vehicle_unavailable: Option<bool>,
+    pub startup_delay_secs: Option<u64>,
+    pub low_battery_threshold_pct: Option<i32>,
+    pub low_battery_poll_interval_secs: Option<u64>,
+    pub poll_interval_driving: Option<u64>,
+    pub poll_interval_fast_charging: Option<u64>,
+    pub poll_interval_ac_charging: Option<u64>,
+    pub poll_interval_parked: Option<u64>,
+    pub poll_interval_unknown: Option<u64>,
+    pub healthz_max_age_secs: Option<u64>,
+    pub vehicle_allowlist: Option<String>,
+    pub vehicle_denylist: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsSection {
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub renames: Option<String>,
+    pub prefix: Option<String>,
+    pub distance_units: Option<String>,
+    pub temp_units: Option<String>,
+    pub sample_interval_secs: Option<u64>,
+    pub staleness_secs: Option<u64>,
+    pub vehicle_data_extra_fields: Option<String>,
+    pub retention_minutes: Option<u64>,
+    pub state_db_path: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSection {
+    pub statsd_host: Option<String>,
+    pub statsd_port: Option<u16>,
+    pub statsd_tags: Option<bool>,
+    pub statsd_prefix: Option<String>,
+    pub statsd_sample_rate: Option<f64>,
+    pub influxdb_url: Option<String>,
+    pub influxdb_bucket: Option<String>,
+    pub influxdb_org: Option<String>,
+    pub influxdb_token: Option<String>,
+    pub webhook_url: Option<String>,
+    pub webhook_auth_header: Option<String>,
+    pub mqtt_broker_url: Option<String>,
+    pub mqtt_username: Option<String>,
+    pub mqtt_password: Option<String>,
+    pub mqtt_qos: Option<u8>,
+    pub mqtt_reconnect_interval_secs: Option<u64>,
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&text)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// The settings of the file as environment variables.
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = Vec::new();
+        let mut add = |name: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                vars.push((name, value));
+            }
+        };
+        let auth = &self.auth;
+        add("TESLA_ACCESS_TOKEN", auth.access_token.clone());
+        add("TESLA_REFRESH_TOKEN", auth.refresh_token.clone());
+        add("TESLA_CERT_PIN_SHA256", auth.cert_pin_sha256.clone());
+        add("TESLA_API_URL", auth.api_url.clone());
+        add("TESLA_AUTH_URL", auth.auth_url.clone());
+        add("TESLA_TOKEN_FILE", auth.token_file.clone());
+        add("TOKEN_RELOAD_INTERVAL_SECS", auth.token_reload_interval_secs.map(|v| v.to_string()));
+        add("TESLA_ACCOUNTS", auth.accounts.as_ref().map(|accounts| serde_json::to_string(accounts).unwrap_or_default()));
+
+        let poller = &self.poller;
+        add("RETRY_ON_VEHICLE_UNAVAILABLE", poller.retry_on_vehicle_unavailable.map(|v| v.to_string()));
+        add("STARTUP_DELAY_SECS", poller.startup_delay_secs.map(|v| v.to_string()));
+        add("LOW_BATTERY_THRESHOLD_PCT", poller.low_battery_threshold_pct.map(|v| v.to_string()));
+        add("LOW_BATTERY_POLL_INTERVAL_SECS", poller.low_battery_poll_interval_secs.map(|v| v.to_string()));
+        add("POLL_INTERVAL_DRIVING", poller.poll_interval_driving.map(|v| v.to_string()));
+        add("POLL_INTERVAL_FAST_CHARGING", poller.poll_interval_fast_charging.map(|v| v.to_string()));
+        add("POLL_INTERVAL_AC_CHARGING", poller.poll_interval_ac_charging.map(|v| v.to_string()));
+        add("POLL_INTERVAL_PARKED", poller.poll_interval_parked.map(|v| v.to_string()));
+        add("POLL_INTERVAL_UNKNOWN", poller.poll_interval_unknown.map(|v| v.to_string()));
+        add("HEALTHZ_MAX_AGE_SECS", poller.healthz_max_age_secs.map(|v| v.to_string()));
+        add("TESLA_VEHICLE_ALLOWLIST", poller.vehicle_allowlist.clone());
+        add("TESLA_VEHICLE_DENYLIST", poller.vehicle_denylist.clone());
+
+        let metrics = &self.metrics;
+        add("METRICS_PORT", metrics.port.map(|v| v.to_string()));
+        add("METRICS_USERNAME", metrics.username.clone());
+        add("METRICS_PASSWORD", metrics.password.clone());
+        add("METRIC_RENAMES", metrics.renames.clone());
+        add("METRIC_PREFIX", metrics.prefix.clone());
+        add("TESLA_DISTANCE_UNITS", metrics.distance_units.clone());
+        add("TESLA_TEMP_UNITS", metrics.temp_units.clone());
+        add("METRICS_SAMPLE_INTERVAL_SECS", metrics.sample_interval_secs.map(|v| v.to_string()));
+        add("METRIC_STALENESS_SECS", metrics.staleness_secs.map(|v| v.to_string()));
+        add("VEHICLE_DATA_EXTRA_FIELDS", metrics.vehicle_data_extra_fields.clone());
+        add("RETENTION_MINUTES", metrics.retention_minutes.map(|v| v.to_string()));
+        add("STATE_DB_PATH", metrics.state_db_path.clone());
+
+        let output = &self.output;
+        add("STATSD_HOST", output.statsd_host.clone());
+        add("STATSD_PORT", output.statsd_port.map(|v| v.to_string()));
+        add("STATSD_TAGS", output.statsd_tags.map(|v| v.to_string()));
+        add("STATSD_PREFIX", output.statsd_prefix.clone());
+        add("STATSD_SAMPLE_RATE", output.statsd_sample_rate.map(|v| v.to_string()));
+        add("INFLUXDB_URL", output.influxdb_url.clone());
+        add("INFLUXDB_BUCKET", output.influxdb_bucket.clone());
+        add("INFLUXDB_ORG", output.influxdb_org.clone());
+        add("INFLUXDB_TOKEN", output.influxdb_token.clone());
+        add("WEBHOOK_URL", output.webhook_url.clone());
+        add("WEBHOOK_AUTH_HEADER", output.webhook_auth_header.clone());
+        add("MQTT_BROKER_URL", output.mqtt_broker_url.clone());
+        add("MQTT_USERNAME", output.mqtt_username.clone());
+        add("MQTT_PASSWORD", output.mqtt_password.clone());
+        add("MQTT_QOS", output.mqtt_qos.map(|v| v.to_string()));
+        add("MQTT_RECONNECT_INTERVAL_SECS", output.mqtt_reconnect_interval_secs.map(|v| v.to_string()));
+        vars
+    }
+
+    /// Sets the environment variables of the file that aren't set yet, so the rest of the exporter
+    /// reads file and environment configuration alike. Returns how many were set.
+    pub fn apply_to_env(&self) -> usize {
+        let mut count = 0;
+        for (name, value) in self.env_vars() {
+            if env::var_os(name).is_none() {
+                env::set_var(name, value);
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_map_config_file_to_env_vars() -> Result<()> {
+        let config: Config = toml::from_str(r#"
+            [auth]
+            refresh_token = "refresh"
+
+            [poller]
+            retry_on_vehicle_unavailable = true
+            poll_interval_parked = 60
+
+            [output]
+            statsd_host = "localhost"
+        "#)?;
+
+        assert_eq!(config.env_vars(), vec![
+            ("TESLA_REFRESH_TOKEN", "refresh".to_string()),
+            ("RETRY_ON_VEHICLE_UNAVAILABLE", "true".to_string()),
+            ("POLL_INTERVAL_PARKED", "60".to_string()),
+            ("STATSD_HOST", "localhost".to_string()),
+        ]);
+        assert!(toml::from_str::<Config>("[metrics]\nunknown = 1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_map_mqtt_settings_to_env_vars() -> Result<()> {
+        let config: Config = toml::from_str(r#"
+            [output]
+            mqtt_broker_url = "mqtt://localhost:1883"
+            mqtt_username = "tesla"
+            mqtt_password = "secret"
+            mqtt_qos = 1
+            mqtt_reconnect_interval_secs = 10
+        "#)?;
+
+        assert_eq!(config.env_vars(), vec![
+            ("MQTT_BROKER_URL", "mqtt://localhost:1883".to_string()),
+            ("MQTT_USERNAME", "tesla".to_string()),
+            ("MQTT_PASSWORD", "secret".to_string()),
+            ("MQTT_QOS", "1".to_string()),
+            ("MQTT_RECONNECT_INTERVAL_SECS", "10".to_string()),
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_map_accounts_to_env_vars() -> Result<()> {
+        let config: Config = toml::from_str(r#"
+            [auth]
+            cert_pin_sha256 = "pin"
+
+            [[auth.accounts]]
+            access_token = "access1"
+            refresh_token = "refresh1"
+
+            [[auth.accounts]]
+            access_token = "access2"
+            refresh_token = "refresh2"
+        "#)?;
+
+        assert_eq!(config.env_vars(), vec![
+            ("TESLA_CERT_PIN_SHA256", "pin".to_string()),
+            ("TESLA_ACCOUNTS", r#"[{"access_token":"access1","refresh_token":"refresh1"},{"access_token":"access2","refresh_token":"refresh2"}]"#.to_string()),
+        ]);
+
+        Ok(())
+    }
+}
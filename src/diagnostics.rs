@@ -0,0 +1,186 @@
+use std::env;
+
+use log::{info, warn};
+use rocket::{get, State};
+use rocket_contrib::json::Json;
+use rocket_prometheus::prometheus::{Encoder, TextEncoder};
+use serde::Serialize;
+
+use crate::poller;
+use crate::poller::PollerConfig;
+use crate::tesla_api_client::dtos::AuthToken;
+use crate::tesla_api_client::TeslaApiClient;
+
+const REQUIRED_ENV_VARS: [&str; 2] = ["TESLA_ACCESS_TOKEN", "TESLA_REFRESH_TOKEN"];
+/// Either replaces `REQUIRED_ENV_VARS` when several accounts are polled.
+const ACCOUNTS_ENV_VARS: [&str; 2] = ["TESLA_ACCOUNTS", "TESLA_ACCESS_TOKEN_1"];
+
+/// The outcome of one self-test.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiagnosticCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    pub message: String,
+}
+
+impl DiagnosticCheck {
+    fn passed(name: &'static str, message: impl Into<String>) -> Self {
+        DiagnosticCheck { name, passed: true, message: message.into() }
+    }
+
+    fn failed(name: &'static str, message: impl Into<String>) -> Self {
+        DiagnosticCheck { name, passed: false, message: message.into() }
+    }
+}
+
+/// The self-tests run on startup, served by `GET /diagnostics`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiagnosticsResult {
+    pub passed: bool,
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+impl From<Vec<DiagnosticCheck>> for DiagnosticsResult {
+    fn from(checks: Vec<DiagnosticCheck>) -> Self {
+        DiagnosticsResult { passed: checks.iter().all(|check| check.passed), checks }
+    }
+}
+
+impl DiagnosticsResult {
+    /// Logs every check, failed checks as warnings.
+    pub fn log(&self) {
+        for check in &self.checks {
+            if check.passed {
+                info!("Diagnostics passed: Check=\"{}\" message=\"{}\"", check.name, check.message);
+            } else {
+                warn!("Diagnostics failed: Check=\"{}\" message=\"{}\"", check.name, check.message);
+            }
+        }
+    }
+}
+
+/// Verifies the credentials, that a vehicle is accessible, the required environment variables and
+/// that the metrics can be rendered.
+pub fn run_diagnostics(client: &TeslaApiClient) -> DiagnosticsResult {
+    let mut checks = check_account(client);
+    checks.push(check_env_vars(env_var));
+    checks.push(check_metrics_encoding());
+    checks.into()
+}
+
+/// Runs the checks of [`run_diagnostics`] for every account in the environment, prefixing the
+/// messages with the account index when several are configured. The API is called after
+/// `STARTUP_DELAY_SECS`, and only the environment variables are checked when no token is
+/// configured.
+pub fn run_startup_diagnostics() -> DiagnosticsResult {
+    let env_vars = check_env_vars(env_var);
+    if !env_vars.passed {
+        return vec![env_vars].into();
+    }
+
+    let accounts = match AuthToken::accounts_from_env() {
+        Ok(accounts) => accounts,
+        Err(err) => return vec![env_vars, DiagnosticCheck::failed("credentials", format!("{:#}", err))].into(),
+    };
+    if let Ok(config) = PollerConfig::from_env() {
+        poller::delay_first_api_call(config.startup_delay);
+    }
+
+    let account_count = accounts.len();
+    let mut checks = Vec::new();
+    for (index, auth_token) in accounts.into_iter().enumerate() {
+        let account_checks = match TeslaApiClient::create(auth_token) {
+            Ok(client) => check_account(&client),
+            Err(err) => vec![DiagnosticCheck::failed("credentials", format!("{:#}", err))],
+        };
+        checks.extend(account_checks.into_iter().map(|mut check| {
+            if account_count > 1 {
+                check.message = format!("Account {}: {}", index + 1, check.message);
+            }
+            check
+        }));
+    }
+    checks.push(env_vars);
+    checks.push(check_metrics_encoding());
+
+    checks.into()
+}
+
+/// The credentials and vehicles checks of one account.
+fn check_account(client: &TeslaApiClient) -> Vec<DiagnosticCheck> {
+    match client.fetch_vehicles() {
+        Ok(vehicles) => vec![
+            DiagnosticCheck::passed("credentials", "Tesla API accepted the access token"),
+            match vehicles.len() {
+                0 => DiagnosticCheck::failed("vehicles", "No vehicles found for the account"),
+                count => DiagnosticCheck::passed("vehicles", format!("{} vehicle(s) accessible", count)),
+            },
+        ],
+        Err(err) => vec![
+            DiagnosticCheck::failed("credentials", format!("{:#}", err)),
+            DiagnosticCheck::failed("vehicles", "Skipped, the vehicles could not be fetched"),
+        ],
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok()
+}
+
+/// Checks the variables looked up with `env_var`, the process environment outside of tests.
+fn check_env_vars(env_var: impl Fn(&str) -> Option<String>) -> DiagnosticCheck {
+    if let Some(name) = ACCOUNTS_ENV_VARS.iter().find(|name| env_var(name).is_some()) {
+        return DiagnosticCheck::passed("env_vars", format!("Accounts are configured with {}", name));
+    }
+
+    let missing: Vec<&str> = REQUIRED_ENV_VARS
+        .iter()
+        .filter(|name| env_var(name).is_none_or(|value| value.is_empty()))
+        .copied()
+        .collect();
+
+    if missing.is_empty() {
+        DiagnosticCheck::passed("env_vars", "All required environment variables are set")
+    } else {
+        DiagnosticCheck::failed("env_vars", format!("Missing environment variables: {}", missing.join(", ")))
+    }
+}
+
+fn check_metrics_encoding() -> DiagnosticCheck {
+    let prometheus = poller::register();
+    let mut buffer = Vec::new();
+    match TextEncoder::new().encode(&prometheus.registry().gather(), &mut buffer) {
+        Ok(_) => DiagnosticCheck::passed("metrics", format!("Encoded {} bytes of metrics", buffer.len())),
+        Err(err) => DiagnosticCheck::failed("metrics", format!("Failed to encode metrics: {}", err)),
+    }
+}
+
+/// `GET /diagnostics`, the result of the startup self-tests as JSON.
+#[get("/")]
+pub fn get_diagnostics(diagnostics: State<DiagnosticsResult>) -> Json<DiagnosticsResult> {
+    Json(diagnostics.inner().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn should_report_missing_env_vars() {
+        let check = |vars: &[(&str, &str)]| {
+            let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+            check_env_vars(|name| vars.get(name).map(|value| value.to_string()))
+        };
+
+        let missing = check(&[("TESLA_ACCESS_TOKEN", "access"), ("TESLA_REFRESH_TOKEN", "")]);
+        assert!(!missing.passed);
+        assert_eq!(missing.name, "env_vars");
+        assert_eq!(missing.message, "Missing environment variables: TESLA_REFRESH_TOKEN");
+
+        assert!(check(&[("TESLA_ACCESS_TOKEN", "access"), ("TESLA_REFRESH_TOKEN", "refresh")]).passed);
+        assert!(check(&[("TESLA_ACCOUNTS", "accounts.json")]).passed);
+        assert!(check_metrics_encoding().passed);
+    }
+}
@@ -1,2 +1,21 @@
+#![feature(proc_macro_hygiene, decl_macro)]
+
+pub mod auth;
+pub mod config;
+pub mod diagnostics;
+pub mod metrics;
+pub mod output;
 pub mod poller;
+pub mod routes;
+pub mod stop_signal;
+pub mod storage;
+#[cfg(feature = "sqlite")]
+pub mod store;
 pub mod tesla_api_client;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod units;
+
+pub mod build_info {
+    include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+}
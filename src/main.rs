@@ -4,10 +4,20 @@ extern crate anyhow;
 extern crate rocket;
 extern crate serde;
 
+use std::env;
+use std::path::Path;
+use std::process;
+
+use clap::{App, Arg};
+
 use dotenv::dotenv;
-use log::{info, warn};
+use log::{error, info, warn};
 use log4rs;
+use rocket::{routes, Rocket};
 
+use tesla_api_exporter::config::Config;
+use tesla_api_exporter::diagnostics;
+use tesla_api_exporter::poller;
 use tesla_api_exporter::poller::Poller;
 
 fn main() {
@@ -19,5 +29,68 @@ fn main() {
 
     info!("Starting up!!!");
 
-    rocket::ignite().attach(Poller::fairing()).launch();
+    let args = App::new("tesla-api-exporter")
+        .arg(Arg::with_name("config")
+            .long("config")
+            .value_name("PATH")
+            .help("TOML config file, environment variables take precedence")
+            .takes_value(true))
+        .arg(Arg::with_name("output")
+            .long("output")
+            .value_name("OUTPUT")
+            .help("Write the metrics to stdout after each poll instead of serving them over HTTP")
+            .possible_values(&["stdout"])
+            .takes_value(true))
+        .get_matches();
+
+    if let Some(path) = args.value_of("config") {
+        match Config::from_file(Path::new(path)) {
+            Ok(config) => info!("Loaded config file: Path=\"{}\" settings=\"{}\"", path, config.apply_to_env()),
+            Err(e) => {
+                error!("{:#}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if args.value_of("output") == Some("stdout") {
+        if let Err(e) = poller::run_standalone() {
+            error!("{:#}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let diagnostics_result = diagnostics::run_startup_diagnostics();
+    diagnostics_result.log();
+
+    with_metrics_port(rocket::ignite())
+        .manage(diagnostics_result)
+        .mount("/diagnostics", routes![diagnostics::get_diagnostics])
+        .attach(Poller::fairing())
+        .launch();
+}
+
+/// Serves on `METRICS_PORT` when set, taking precedence over `ROCKET_PORT` and Rocket.toml.
+fn with_metrics_port(rocket: Rocket) -> Rocket {
+    let metrics_port = match env::var("METRICS_PORT") {
+        Err(_) => return rocket,
+        Ok(metrics_port) => metrics_port,
+    };
+    let port: u16 = match metrics_port.trim().parse() {
+        Ok(port) => port,
+        Err(_) => {
+            warn!("Ignoring invalid METRICS_PORT=\"{}\"", metrics_port);
+            return rocket;
+        }
+    };
+
+    let mut config = rocket.config().clone();
+    if config.port != port {
+        if let Ok(rocket_port) = env::var("ROCKET_PORT") {
+            warn!("METRICS_PORT=\"{}\" overrides ROCKET_PORT=\"{}\"", port, rocket_port);
+        }
+    }
+    config.set_port(port);
+    rocket::custom(config)
 }
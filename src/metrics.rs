@@ -0,0 +1,98 @@
+//! Naming of the exported metrics, shared by the poller and the Tesla API client.
+
+use std::collections::HashMap;
+use std::env;
+
+use log::warn;
+use once_cell::sync::Lazy;
+use rocket_prometheus::prometheus::Opts;
+
+/// Renames metrics so existing dashboards keep working when metric names change.
+///
+/// Configured via `METRIC_RENAMES`, a comma-separated list of `old_name=new_name` pairs, e.g.
+/// `METRIC_RENAMES="tesla_drive_state_speed=tesla_speed,tesla_is_online=tesla_online"`.
+/// `METRIC_PREFIX` replaces the `tesla` prefix of the metrics that aren't renamed.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsNameMapper {
+    renames: HashMap<String, String>,
+    prefix: Option<String>,
+}
+
+impl MetricsNameMapper {
+    const DEFAULT_PREFIX: &'static str = "tesla";
+
+    pub fn from_env() -> Self {
+        let mapper = MetricsNameMapper::parse(&env::var("METRIC_RENAMES").unwrap_or_default());
+        match env::var("METRIC_PREFIX") {
+            Ok(prefix) => mapper.with_prefix(&prefix),
+            Err(_) => mapper,
+        }
+    }
+
+    pub fn parse(renames: &str) -> Self {
+        let renames = renames
+            .split(',')
+            .filter_map(|pair| {
+                let mut parts = pair.splitn(2, '=');
+                let old_name = parts.next()?.trim();
+                let new_name = parts.next()?.trim();
+                if old_name.is_empty() || new_name.is_empty() {
+                    warn!("Ignoring invalid metric rename: \"{}\"", pair);
+                    return None;
+                }
+                Some((old_name.to_string(), new_name.to_string()))
+            })
+            .collect();
+
+        MetricsNameMapper { renames, prefix: None }
+    }
+
+    /// Ignores prefixes that aren't valid Prometheus metric names, e.g. `my-exporter`.
+    pub fn with_prefix(self, prefix: &str) -> Self {
+        let prefix = prefix.trim();
+        let is_valid = prefix.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+            && prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+        if !is_valid {
+            warn!("Ignoring invalid METRIC_PREFIX=\"{}\", using \"{}\"", prefix, MetricsNameMapper::DEFAULT_PREFIX);
+            return self;
+        }
+        MetricsNameMapper { prefix: Some(prefix.to_string()), ..self }
+    }
+
+    pub fn apply(&self, name: &str) -> String {
+        if let Some(new_name) = self.renames.get(name) {
+            return new_name.clone();
+        }
+        match (&self.prefix, name.strip_prefix(MetricsNameMapper::DEFAULT_PREFIX)) {
+            (Some(prefix), Some(rest)) if rest.starts_with('_') => format!("{}{}", prefix, rest),
+            _ => name.to_string(),
+        }
+    }
+}
+
+static METRICS_NAME_MAPPER: Lazy<MetricsNameMapper> = Lazy::new(MetricsNameMapper::from_env);
+
+/// Options of a metric named `name` after applying `METRIC_RENAMES` and `METRIC_PREFIX`.
+pub(crate) fn metric_opts(name: &str, help: &str) -> Opts {
+    Opts::new(METRICS_NAME_MAPPER.apply(name), help)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_rename_mapped_metrics_only() {
+        let mapper = MetricsNameMapper::parse("tesla_drive_state_speed=tesla_speed, tesla_is_online = tesla_online,bogus");
+
+        assert_eq!(mapper.apply("tesla_drive_state_speed"), "tesla_speed");
+        assert_eq!(mapper.apply("tesla_is_online"), "tesla_online");
+        assert_eq!(mapper.apply("tesla_car_state"), "tesla_car_state");
+        assert_eq!(mapper.apply("bogus"), "bogus");
+
+        let mapper = mapper.with_prefix("home_tesla");
+        assert_eq!(mapper.apply("tesla_car_state"), "home_tesla_car_state");
+        assert_eq!(mapper.apply("tesla_drive_state_speed"), "tesla_speed");
+        assert_eq!(mapper.with_prefix("my-exporter").apply("tesla_car_state"), "home_tesla_car_state");
+    }
+}
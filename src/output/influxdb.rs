@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use ureq::Agent;
+
+use crate::output::{samples, Output};
+use crate::tesla_api_client::dtos::VehicleData;
+
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Writes every recorded value to InfluxDB 2.x in line protocol, one point per state section
+/// tagged with `car_name`, e.g. `tesla_charge_state,car_name=My\ Car battery_level=80`.
+pub struct InfluxDbOutput {
+    agent: Agent,
+    write_url: String,
+    token: String,
+}
+
+impl InfluxDbOutput {
+    /// `None` unless `INFLUXDB_URL` is set, `INFLUXDB_BUCKET`, `INFLUXDB_ORG` and `INFLUXDB_TOKEN` are
+    /// then required.
+    pub fn from_env() -> Result<Option<Self>> {
+        let base_url = match env::var("INFLUXDB_URL") {
+            Ok(base_url) => base_url,
+            Err(_) => return Ok(None),
+        };
+        let required = |name: &str| env::var(name).with_context(|| format!("INFLUXDB_URL is set but {} is missing", name));
+        Ok(Some(InfluxDbOutput::new(&base_url, &required("INFLUXDB_BUCKET")?, &required("INFLUXDB_ORG")?,
+                                    &required("INFLUXDB_TOKEN")?)))
+    }
+
+    pub fn new(base_url: &str, bucket: &str, org: &str, token: &str) -> Self {
+        let agent = ureq::AgentBuilder::new().timeout(WRITE_TIMEOUT).build();
+        let write_url = format!("{}/api/v2/write?org={}&bucket={}&precision=ns",
+                                base_url.trim_end_matches('/'), encode_query(org), encode_query(bucket));
+        InfluxDbOutput { agent, write_url, token: token.to_string() }
+    }
+}
+
+impl Output for InfluxDbOutput {
+    fn publish(&self, vehicle_data: &VehicleData) -> Result<()> {
+        let points: Vec<InfluxDbPoint> = vehicle_data.into();
+        let lines: Vec<String> = points.iter().map(InfluxDbPoint::to_string).collect();
+
+        self.agent.post(&self.write_url)
+            .set("Authorization", &format!("Token {}", self.token))
+            .set("Content-Type", "text/plain; charset=utf-8")
+            .send_string(&lines.join("\n"))
+            .with_context(|| format!("Failed to write to InfluxDB: Vehicle=\"{}\"", vehicle_data.display_name))?;
+        Ok(())
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, org and bucket names may contain spaces.
+fn encode_query(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// A point in InfluxDB line protocol, written with `Display`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InfluxDbPoint {
+    pub measurement: String,
+    pub tags: BTreeMap<String, String>,
+    pub fields: BTreeMap<String, f64>,
+    pub timestamp_ns: i64,
+}
+
+/// One point per state section, e.g. `tesla_charge_state`, timestamped with that section's
+/// `timestamp`.
+impl From<&VehicleData> for Vec<InfluxDbPoint> {
+    fn from(vehicle_data: &VehicleData) -> Self {
+        let mut points: BTreeMap<&str, InfluxDbPoint> = BTreeMap::new();
+
+        for sample in samples(vehicle_data) {
+            let (section, field) = match sample.name {
+                [section, field] => (*section, *field),
+                _ => continue,
+            };
+            let timestamp_ms = match section {
+                "charge_state" => vehicle_data.charge_state.timestamp,
+                "climate_state" => vehicle_data.climate_state.timestamp,
+                "drive_state" => vehicle_data.drive_state.timestamp,
+                "vehicle_state" => vehicle_data.vehicle_state.timestamp,
+                _ => continue,
+            };
+
+            points
+                .entry(section)
+                .or_insert_with(|| InfluxDbPoint {
+                    measurement: format!("tesla_{}", section),
+                    tags: [
+                        ("car_name".to_string(), vehicle_data.display_name.clone()),
+                        ("vehicle_id".to_string(), vehicle_data.id.to_string()),
+                    ].iter().cloned().collect(),
+                    fields: BTreeMap::new(),
+                    timestamp_ns: timestamp_ms * 1_000_000,
+                })
+                .fields
+                .insert(field.to_string(), sample.value);
+        }
+
+        points.into_values().collect()
+    }
+}
+
+impl Display for InfluxDbPoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", escape(&self.measurement, &[',', ' ']))?;
+        for (key, value) in &self.tags {
+            write!(f, ",{}={}", escape(key, &[',', '=', ' ']), escape(value, &[',', '=', ' ']))?;
+        }
+        for (i, (key, value)) in self.fields.iter().enumerate() {
+            let separator = if i == 0 { ' ' } else { ',' };
+            write!(f, "{}{}={}", separator, escape(key, &[',', '=', ' ']), value)?;
+        }
+        write!(f, " {}", self.timestamp_ns)
+    }
+}
+
+fn escape(text: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn should_write_one_point_per_state() -> Result<()> {
+        let vehicle_data: VehicleData = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+
+        let points: Vec<InfluxDbPoint> = (&vehicle_data).into();
+        let measurements: Vec<&str> = points.iter().map(|point| point.measurement.as_str()).collect();
+
+        assert_eq!(measurements, vec!["tesla_charge_state", "tesla_climate_state", "tesla_drive_state", "tesla_vehicle_state"]);
+        assert_eq!(points[0].fields.get("battery_level"), Some(&87.0));
+        assert_eq!(
+            points[3].to_string(),
+            "tesla_vehicle_state,car_name=Bellwood\\ Auto,vehicle_id=41614331478102467 \
+             is_user_present=0,locked=1,odometer=7469.486058,software_update_expected_duration_sec=2700,valet_mode=0 \
+             1609734298988000000"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_post_line_protocol_to_write_api() -> Result<()> {
+        let vehicle_data: VehicleData = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let base_url = format!("http://{}/", listener.local_addr()?);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            write!(reader.get_mut(), "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+
+        InfluxDbOutput::new(&base_url, "tesla", "my org", "secret").publish(&vehicle_data)?;
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /api/v2/write?org=my%20org&bucket=tesla&precision=ns "));
+        assert!(head.contains("Authorization: Token secret"));
+        assert_eq!(body.lines().count(), 4);
+        assert!(body.lines().next().unwrap().starts_with("tesla_charge_state,car_name=Bellwood\\ Auto,"));
+
+        Ok(())
+    }
+}
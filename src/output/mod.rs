@@ -0,0 +1,81 @@
+//! Backends receiving vehicle data and car state changes next to `/metrics`.
+//!
+//! Publishers (StatsD, InfluxDB, MQTT) and notifiers (the webhook) all implement [`Output`] and
+//! live here, so the poller has a single list to feed instead of one per kind of backend.
+
+use anyhow::Result;
+use log::warn;
+
+use crate::poller::{CarState, VEHICLE_DATA_FIELDS};
+use crate::tesla_api_client::dtos::VehicleData;
+
+pub mod influxdb;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod statsd;
+pub mod stdout;
+pub mod webhook;
+
+/// A backend that receives vehicle data after it was recorded, in addition to `/metrics`.
+pub trait Output: Send + Sync {
+    fn publish(&self, vehicle_data: &VehicleData) -> Result<()>;
+
+    /// Called when a vehicle's `CarState` changed to another variant, most outputs only publish
+    /// vehicle data.
+    fn publish_car_state(&self, _car_name: &str, _previous_state: &CarState, _car_state: &CarState) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A metric value, `name` holds the segments of the metric name after the `tesla` prefix so each
+/// backend can join them with its own separator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: &'static [&'static str],
+    pub value: f64,
+}
+
+impl Sample {
+    pub fn name(&self, separator: &str) -> String {
+        self.name.join(separator)
+    }
+}
+
+/// The values `record()` exports to Prometheus, for backends that don't read the registry. Distances and
+/// temperatures are converted to `UNITS` the same way.
+pub fn samples(vehicle_data: &VehicleData) -> Vec<Sample> {
+    VEHICLE_DATA_FIELDS
+        .iter()
+        .map(|field| Sample { name: field.name, value: (field.value)(vehicle_data) })
+        .collect()
+}
+
+/// The outputs enabled through environment variables, outputs that fail to start are skipped.
+pub fn from_env() -> Vec<Box<dyn Output>> {
+    let mut outputs: Vec<Box<dyn Output>> = Vec::new();
+
+    match statsd::StatsdOutput::from_env() {
+        Ok(None) => {}
+        Ok(Some(output)) => outputs.push(Box::new(output)),
+        Err(err) => warn!("Failed to start StatsD output: {:#}", err),
+    }
+
+    match influxdb::InfluxDbOutput::from_env() {
+        Ok(None) => {}
+        Ok(Some(output)) => outputs.push(Box::new(output)),
+        Err(err) => warn!("Failed to start InfluxDB output: {:#}", err),
+    }
+
+    if let Some(output) = webhook::WebhookOutput::from_env() {
+        outputs.push(Box::new(output));
+    }
+
+    #[cfg(feature = "mqtt")]
+    match mqtt::MqttOutput::from_env() {
+        Ok(None) => {}
+        Ok(Some(output)) => outputs.push(Box::new(output)),
+        Err(err) => warn!("Failed to start MQTT output: {:#}", err),
+    }
+
+    outputs
+}
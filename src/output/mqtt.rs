@@ -0,0 +1,142 @@
+use std::env;
+use std::thread;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use rumqttc::{Client, MqttOptions, QoS};
+
+use crate::output::{samples, Output};
+use crate::poller::CarState;
+use crate::tesla_api_client::dtos::VehicleData;
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_RECONNECT_INTERVAL_SECS: u64 = 5;
+/// Publish requests queued while the broker is unreachable, further publishes fail.
+const REQUEST_CAPACITY: usize = 100;
+
+/// Publishes vehicle data to an MQTT broker, the full `VehicleData` as JSON to
+/// `tesla/<car_name>/state`, every recorded value to its own topic, e.g.
+/// `tesla/<car_name>/charge/battery_level`, and `CarState` changes to `tesla/<car_name>/car_state`.
+///
+/// The connection is driven on a background thread that reconnects every `reconnect_interval`
+/// after the broker dropped it.
+pub struct MqttOutput {
+    client: Client,
+    qos: QoS,
+}
+
+impl MqttOutput {
+    /// `None` unless `MQTT_BROKER_URL` is set, e.g. `mqtt://localhost:1883`. `MQTT_USERNAME` and
+    /// `MQTT_PASSWORD` are optional, `MQTT_QOS` defaults to 0 and `MQTT_RECONNECT_INTERVAL_SECS` to 5.
+    pub fn from_env() -> Result<Option<Self>> {
+        let broker_url = match env::var("MQTT_BROKER_URL") {
+            Ok(broker_url) => broker_url,
+            Err(_) => return Ok(None),
+        };
+        let (host, port) = parse_broker_url(&broker_url)?;
+
+        let mut options = MqttOptions::new("tesla-api-exporter", host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Ok(username) = env::var("MQTT_USERNAME") {
+            options.set_credentials(username, env::var("MQTT_PASSWORD").unwrap_or_default());
+        }
+
+        let qos = match env::var("MQTT_QOS") {
+            Ok(qos) => parse_qos(&qos)?,
+            Err(_) => QoS::AtMostOnce,
+        };
+        let reconnect_interval = match env::var("MQTT_RECONNECT_INTERVAL_SECS") {
+            Ok(secs) => secs.trim().parse().with_context(|| format!("Invalid MQTT_RECONNECT_INTERVAL_SECS \"{}\"", secs))?,
+            Err(_) => DEFAULT_RECONNECT_INTERVAL_SECS,
+        };
+
+        Ok(Some(MqttOutput::new(options, qos, Duration::from_secs(reconnect_interval))))
+    }
+
+    pub fn new(options: MqttOptions, qos: QoS, reconnect_interval: Duration) -> Self {
+        let (client, mut connection) = Client::new(options, REQUEST_CAPACITY);
+        thread::spawn(move || {
+            for notification in connection.iter() {
+                if let Err(err) = notification {
+                    warn!("MQTT connection failed, reconnecting: Waiting=\"{:?}\" error=\"{}\"", reconnect_interval, err);
+                    sleep(reconnect_interval);
+                }
+            }
+            info!("MQTT connection closed");
+        });
+        MqttOutput { client, qos }
+    }
+
+    fn send(&self, topic: String, payload: impl Into<Vec<u8>>) -> Result<()> {
+        self.client.publish(topic.as_str(), self.qos, false, payload)
+            .with_context(|| format!("Failed to publish MQTT message: Topic=\"{}\"", topic))
+    }
+}
+
+impl Output for MqttOutput {
+    fn publish(&self, vehicle_data: &VehicleData) -> Result<()> {
+        let car_name = topic_segment(&vehicle_data.display_name);
+
+        let state = serde_json::to_vec(vehicle_data).context("Failed to serialize vehicle data")?;
+        self.send(format!("tesla/{}/state", car_name), state)?;
+
+        for sample in samples(vehicle_data) {
+            let topic = match sample.name {
+                [section, field] => format!("tesla/{}/{}/{}", car_name, section.trim_end_matches("_state"), field),
+                _ => format!("tesla/{}/{}", car_name, sample.name("/")),
+            };
+            self.send(topic, sample.value.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn publish_car_state(&self, car_name: &str, _previous_state: &CarState, car_state: &CarState) -> Result<()> {
+        self.send(format!("tesla/{}/car_state", topic_segment(car_name)), car_state.to_string())
+    }
+}
+
+/// `host` or `host:port`, optionally prefixed with `mqtt://` or `tcp://`.
+fn parse_broker_url(url: &str) -> Result<(String, u16)> {
+    let address = url.trim().trim_start_matches("mqtt://").trim_start_matches("tcp://").trim_end_matches('/');
+    match address.rsplit_once(':') {
+        None if !address.is_empty() => Ok((address.to_string(), DEFAULT_PORT)),
+        Some((host, port)) if !host.is_empty() => {
+            let port = port.parse().with_context(|| format!("Invalid MQTT_BROKER_URL \"{}\"", url))?;
+            Ok((host.to_string(), port))
+        }
+        _ => Err(anyhow!("Invalid MQTT_BROKER_URL \"{}\"", url)),
+    }
+}
+
+fn parse_qos(qos: &str) -> Result<QoS> {
+    match qos.trim() {
+        "0" => Ok(QoS::AtMostOnce),
+        "1" => Ok(QoS::AtLeastOnce),
+        "2" => Ok(QoS::ExactlyOnce),
+        _ => Err(anyhow!("Invalid MQTT_QOS \"{}\", expected 0, 1 or 2", qos)),
+    }
+}
+
+/// MQTT wildcards and the level separator can't appear in a topic level.
+fn topic_segment(name: &str) -> String {
+    name.replace(['/', '+', '#'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_broker_url() -> Result<()> {
+        assert_eq!(parse_broker_url("mqtt://broker.local:8883")?, ("broker.local".to_string(), 8883));
+        assert_eq!(parse_broker_url("localhost")?, ("localhost".to_string(), DEFAULT_PORT));
+        assert!(parse_broker_url("mqtt://").is_err());
+        assert_eq!(parse_qos("1")?, QoS::AtLeastOnce);
+        assert!(parse_qos("3").is_err());
+        assert_eq!(topic_segment("My/Car #1"), "My_Car _1");
+
+        Ok(())
+    }
+}
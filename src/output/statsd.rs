@@ -0,0 +1,109 @@
+use std::env;
+use std::net::UdpSocket;
+
+use anyhow::{Context, Result};
+use cadence::prelude::*;
+use cadence::{BufferedUdpMetricSink, StatsdClient};
+
+use crate::output::{samples, Output};
+use crate::poller::env_flag;
+use crate::tesla_api_client::dtos::VehicleData;
+use crate::tesla_api_client::retry::random_fraction;
+
+/// StatsD names are dot separated, unlike the `_` separated Prometheus names.
+const METRIC_SEPARATOR: &str = ".";
+/// Gauges are batched into packets of up to this many bytes, the payload of a UDP datagram on
+/// a 1500 byte MTU Ethernet link.
+const MAX_PACKET_BYTES: usize = 1472;
+
+/// Sends every recorded value as a StatsD gauge, e.g. `tesla.charge_state.battery_level`.
+///
+/// The vehicle is sent as a Datadog style `car_name` tag when `STATSD_TAGS=true`, otherwise it is
+/// part of the metric name for plain StatsD/Graphite, e.g. `tesla.My_Car.charge_state.battery_level`.
+///
+/// With a `sample_rate` below 1 only that share of the polls is sent, all gauges of a poll or none.
+pub struct StatsdOutput {
+    client: StatsdClient,
+    tags: bool,
+    sample_rate: f64,
+}
+
+impl StatsdOutput {
+    /// `None` unless `STATSD_HOST` is set. `STATSD_PORT` defaults to 8125, `STATSD_PREFIX` to `tesla`
+    /// and `STATSD_SAMPLE_RATE` to 1.
+    pub fn from_env() -> Result<Option<Self>> {
+        let host = match env::var("STATSD_HOST") {
+            Ok(host) => host,
+            Err(_) => return Ok(None),
+        };
+        let port: u16 = match env::var("STATSD_PORT") {
+            Ok(port) => port.trim().parse().with_context(|| format!("Invalid STATSD_PORT \"{}\"", port))?,
+            Err(_) => 8125,
+        };
+        let prefix = env::var("STATSD_PREFIX").unwrap_or_else(|_| "tesla".to_string());
+        let sample_rate: f64 = match env::var("STATSD_SAMPLE_RATE") {
+            Ok(rate) => rate.trim().parse().ok().filter(|rate| (0.0..=1.0).contains(rate))
+                .with_context(|| format!("Invalid STATSD_SAMPLE_RATE \"{}\", expected 0 to 1", rate))?,
+            Err(_) => 1.0,
+        };
+        Ok(Some(StatsdOutput::new(&host, port, &prefix, env_flag("STATSD_TAGS"), sample_rate)?))
+    }
+
+    pub fn new(host: &str, port: u16, prefix: &str, tags: bool, sample_rate: f64) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0").context("Failed to bind StatsD socket")?;
+        socket.set_nonblocking(true).context("Failed to configure StatsD socket")?;
+        let sink = BufferedUdpMetricSink::with_capacity((host, port), socket, MAX_PACKET_BYTES)
+            .with_context(|| format!("Failed to resolve StatsD host {}:{}", host, port))?;
+
+        Ok(StatsdOutput { client: StatsdClient::from_sink(prefix, sink), tags, sample_rate })
+    }
+}
+
+impl Output for StatsdOutput {
+    fn publish(&self, vehicle_data: &VehicleData) -> Result<()> {
+        if self.sample_rate < 1.0 && random_fraction() >= self.sample_rate {
+            return Ok(());
+        }
+
+        let car_name = vehicle_data.display_name.as_str();
+        let car_segment = name_segment(car_name);
+        let tag_car_name = tag_value(car_name);
+
+        for sample in samples(vehicle_data) {
+            let name = if self.tags {
+                sample.name(METRIC_SEPARATOR)
+            } else {
+                [car_segment.as_str(), &sample.name(METRIC_SEPARATOR)].join(METRIC_SEPARATOR)
+            };
+
+            let gauge = self.client.gauge_with_tags(&name, sample.value);
+            let gauge = if self.tags { gauge.with_tag("car_name", &tag_car_name) } else { gauge };
+            gauge.try_send()
+                .with_context(|| format!("Failed to send StatsD gauge {}", name))?;
+        }
+        self.client.flush().context("Failed to send StatsD gauges")?;
+        Ok(())
+    }
+}
+
+/// The name separator and the `:`, `|` and `@` delimiting value, type and sample rate can't appear in
+/// a metric name segment.
+fn name_segment(name: &str) -> String {
+    name.replace(|c: char| matches!(c, '.' | ':' | '|' | '@') || c.is_whitespace(), "_")
+}
+
+/// The `,` separating tags and the `|` and `@` delimiting fields can't appear in a tag value.
+fn tag_value(value: &str) -> String {
+    value.replace([',', '|', '@'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_escape_statsd_delimiters() {
+        assert_eq!(name_segment("My Car: 2.0|@home"), "My_Car__2_0__home");
+        assert_eq!(tag_value("My Car: 2.0|@home,work"), "My Car: 2.0__home_work");
+    }
+}
@@ -0,0 +1,58 @@
+use std::io;
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use rocket_prometheus::prometheus::{Encoder, Registry, TextEncoder};
+
+use crate::output::Output;
+use crate::tesla_api_client::dtos::VehicleData;
+
+/// Writes every metric of `registry` to stdout in the Prometheus text format after each poll,
+/// e.g. for `tesla-api-exporter --output stdout | promtool check metrics`.
+pub struct StdoutOutput {
+    registry: Registry,
+}
+
+impl StdoutOutput {
+    pub fn new(registry: Registry) -> Self {
+        StdoutOutput { registry }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .context("Failed to encode metrics")?;
+        Ok(buffer)
+    }
+}
+
+impl Output for StdoutOutput {
+    fn publish(&self, _vehicle_data: &VehicleData) -> Result<()> {
+        let buffer = self.encode()?;
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&buffer).context("Failed to write metrics to stdout")?;
+        stdout.flush().context("Failed to write metrics to stdout")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket_prometheus::prometheus::{IntGauge, Opts};
+
+    use super::*;
+
+    #[test]
+    fn should_encode_registered_metrics() -> Result<()> {
+        let registry = Registry::new();
+        let gauge = IntGauge::with_opts(Opts::new("tesla_test_gauge", "Test gauge"))?;
+        registry.register(Box::new(gauge.clone()))?;
+        gauge.set(42);
+
+        let text = String::from_utf8(StdoutOutput::new(registry).encode()?)?;
+
+        assert!(text.contains("tesla_test_gauge 42"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use ureq::Agent;
+
+use crate::output::Output;
+use crate::poller::CarState;
+use crate::tesla_api_client::dtos::VehicleData;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The JSON body POSTed to `WEBHOOK_URL`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CarStateChange {
+    pub car_name: String,
+    pub previous_state: String,
+    pub new_state: String,
+    /// Milliseconds since the epoch.
+    pub timestamp: u128,
+}
+
+/// Notifies a webhook whenever a vehicle's `CarState` changes, e.g. from `Parked` to `Driving`.
+pub struct WebhookOutput {
+    agent: Agent,
+    url: String,
+    auth_header: Option<String>,
+}
+
+impl WebhookOutput {
+    /// `None` unless `WEBHOOK_URL` is set. `WEBHOOK_AUTH_HEADER` is sent as the `Authorization`
+    /// header, e.g. `Bearer abc`.
+    pub fn from_env() -> Option<Self> {
+        let url = env::var("WEBHOOK_URL").ok()?;
+        Some(WebhookOutput::new(&url, env::var("WEBHOOK_AUTH_HEADER").ok()))
+    }
+
+    pub fn new(url: &str, auth_header: Option<String>) -> Self {
+        let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+        WebhookOutput { agent, url: url.to_string(), auth_header }
+    }
+}
+
+impl Output for WebhookOutput {
+    fn publish(&self, _vehicle_data: &VehicleData) -> Result<()> {
+        Ok(())
+    }
+
+    fn publish_car_state(&self, car_name: &str, previous_state: &CarState, car_state: &CarState) -> Result<()> {
+        let change = CarStateChange {
+            car_name: car_name.to_string(),
+            previous_state: previous_state.to_string(),
+            new_state: car_state.to_string(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis(),
+        };
+
+        let mut request = self.agent.post(&self.url);
+        if let Some(auth_header) = &self.auth_header {
+            request = request.set("Authorization", auth_header);
+        }
+        request.send_json(serde_json::to_value(&change)?)
+            .with_context(|| format!("Failed to call webhook: Url=\"{}\"", self.url))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use serde_json::Value;
+
+    use crate::tesla_api_client::dtos::ShiftState;
+
+    use super::*;
+
+    fn epoch_millis() -> u128 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+    }
+
+    #[test]
+    fn should_post_car_state_change() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/hook", listener.local_addr()?);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            write!(reader.get_mut(), "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").unwrap();
+            (head, body)
+        });
+
+        let parked: VehicleData = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        let mut driving = parked.clone();
+        driving.drive_state.shift_state = Some(ShiftState::Drive);
+        driving.drive_state.speed = Some(42.0);
+
+        let before = epoch_millis();
+        WebhookOutput::new(&url, Some("Bearer secret".to_string()))
+            .publish_car_state("Bellwood Auto", &CarState::from(parked), &CarState::from(driving))?;
+        let after = epoch_millis();
+
+        let (head, body) = server.join().unwrap();
+        let body: Value = serde_json::from_slice(&body)?;
+        assert!(head.starts_with("POST /hook "));
+        assert!(head.contains("Authorization: Bearer secret"));
+        assert_eq!(body["car_name"], "Bellwood Auto");
+        assert_eq!(body["previous_state"], "Parked");
+        assert_eq!(body["new_state"], "Driving");
+        let timestamp = body["timestamp"].as_u64().expect("timestamp is not a number") as u128;
+        assert!(before <= timestamp && timestamp <= after);
+
+        Ok(())
+    }
+}
@@ -1,143 +1,847 @@
 use core::fmt;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::env;
 use std::fmt::{Display, Formatter};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::ops::RangeInclusive;
+use std::process;
+use std::sync::{Arc, mpsc, Mutex, Once, RwLock};
 use std::thread;
 use std::thread::{JoinHandle, sleep};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::{Result};
+use anyhow::{anyhow, Context, Result};
 use log::{error, info, warn};
 use once_cell::sync::Lazy;
 use rocket::fairing::{Fairing, Info, Kind};
-use rocket::Rocket;
+use rocket::{routes, Rocket};
 use rocket_prometheus::{
-    prometheus::{IntGaugeVec, opts},
+    prometheus::IntGaugeVec,
     PrometheusMetrics,
 };
-use rocket_prometheus::prometheus::GaugeVec;
+use rocket_prometheus::prometheus::{CounterVec, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec};
+use rocket_prometheus::prometheus::core::Collector;
+use serde::Serialize;
+use typed_builder::TypedBuilder;
+
+use crate::auth::BasicAuthGuard;
+use crate::build_info;
+use crate::metrics::metric_opts;
+use crate::output;
+use crate::output::Output;
+use crate::output::stdout::StdoutOutput;
+use crate::routes;
+use crate::stop_signal::StopSignal;
+#[cfg(feature = "sqlite")]
+use crate::store::STATE_STORE;
+use crate::storage;
+use crate::storage::{MetricSnapshot, TIME_SERIES_STORE};
+use crate::tesla_api_client::{TeslaApiClient, TeslaApiClientTrait, WakeResult, API_CALL_DURATION_HISTOGRAM};
+use crate::tesla_api_client::circuit_breaker::CircuitBreaker;
+use crate::tesla_api_client::retry::RetryPolicy;
+use crate::tesla_api_client::token_store::FileTokenStore;
+use crate::tesla_api_client::token_refresh_scheduler::TokenRefreshScheduler;
+use crate::tesla_api_client::token_watcher::TokenWatcher;
+use crate::tesla_api_client::dtos::{
+    AuthToken, ChargingState, EnergySiteStatus, Product, TeslaApiError, Vehicle, VehicleChargeState, VehicleData,
+    VehicleDriveState, SHIFT_STATE_UNKNOWN_VALUE,
+};
+use crate::units::UNITS;
+
+/// Poller behaviour that can be tuned through environment variables.
+///
+/// Tests can build one with `PollerConfig::builder()`, any field left unset takes its default.
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct PollerConfig {
+    /// Wake the vehicle and retry once when `vehicle_data` reports the vehicle as unavailable.
+    #[builder(default)]
+    pub retry_on_vehicle_unavailable: bool,
+    /// How long to wait before the first API call, so crash loops don't hammer the Tesla API.
+    #[builder(default)]
+    pub startup_delay: Duration,
+    /// Minimum time between `record()` calls, zero records every poll.
+    #[builder(default)]
+    pub metrics_sample_interval: Duration,
+    /// How long an offline vehicle keeps its last known values before its gauges are zeroed,
+    /// zero keeps them forever.
+    #[builder(default = Duration::from_secs(300))]
+    pub metric_staleness: Duration,
+    /// How often the token store is re-read for a token rotated by another process, zero never re-reads it.
+    #[builder(default)]
+    pub token_reload_interval: Duration,
+    /// Parked vehicles with a battery level below this are polled every `low_battery_poll_interval`.
+    #[builder(default = 10)]
+    pub low_battery_threshold_pct: i32,
+    #[builder(default = Duration::from_secs(60))]
+    pub low_battery_poll_interval: Duration,
+    #[builder(default)]
+    pub poll_intervals: PollIntervals,
+    /// `GET /healthz` fails once no vehicle has been polled successfully for this long.
+    #[builder(default = Duration::from_secs(300))]
+    pub healthz_max_age: Duration,
+}
+
+impl PollerConfig {
+    /// Fails if a `POLL_INTERVAL_*` variable is out of range, see [`PollIntervals::from_env`].
+    pub fn from_env() -> Result<Self> {
+        Ok(PollerConfig::builder()
+            .retry_on_vehicle_unavailable(env_flag("RETRY_ON_VEHICLE_UNAVAILABLE"))
+            .startup_delay(Duration::from_secs(env_secs("STARTUP_DELAY_SECS", 0)))
+            .metrics_sample_interval(Duration::from_secs(env_secs("METRICS_SAMPLE_INTERVAL_SECS", 0)))
+            .metric_staleness(Duration::from_secs(env_secs("METRIC_STALENESS_SECS", 300)))
+            .token_reload_interval(Duration::from_secs(env_secs("TOKEN_RELOAD_INTERVAL_SECS", 0)))
+            .low_battery_threshold_pct(env_pct("LOW_BATTERY_THRESHOLD_PCT", 10))
+            .low_battery_poll_interval(Duration::from_secs(env_secs("LOW_BATTERY_POLL_INTERVAL_SECS", 60)))
+            .poll_intervals(PollIntervals::from_env()?)
+            .healthz_max_age(Duration::from_secs(env_secs("HEALTHZ_MAX_AGE_SECS", 300)))
+            .build())
+    }
+}
+
+impl Default for PollerConfig {
+    fn default() -> Self {
+        PollerConfig::builder().build()
+    }
+}
+
+/// How long to wait between polls in each `CarState`, in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollIntervals {
+    pub driving_secs: u64,
+    pub fast_charging_secs: u64,
+    pub ac_charging_secs: u64,
+    pub parked_secs: u64,
+    pub unknown_secs: u64,
+}
+
+impl PollIntervals {
+    const RANGE_SECS: RangeInclusive<u64> = 1..=3600;
+
+    /// Reads `POLL_INTERVAL_DRIVING`, `POLL_INTERVAL_FAST_CHARGING`, `POLL_INTERVAL_AC_CHARGING`,
+    /// `POLL_INTERVAL_PARKED` and `POLL_INTERVAL_UNKNOWN`, unset variables keep their default.
+    /// `TESLA_POLL_<STATE>_SECS`, e.g. `TESLA_POLL_PARKED_SECS`, is read when `POLL_INTERVAL_<STATE>`
+    /// isn't set.
+    ///
+    /// Fails if a value isn't a number of seconds between 1 and 3600.
+    pub fn from_env() -> Result<Self> {
+        PollIntervals::from_lookup(|name| env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let defaults = PollIntervals::default();
+        let secs = |state: &str, default: u64| -> Result<u64> {
+            let primary = format!("POLL_INTERVAL_{}", state);
+            let alias = format!("TESLA_POLL_{}_SECS", state);
+            let (name, value) = match (lookup(&primary), lookup(&alias)) {
+                (Some(value), _) => (primary, value),
+                (None, Some(value)) => (alias, value),
+                (None, None) => return Ok(default),
+            };
+            match value.trim().parse() {
+                Ok(secs) if PollIntervals::RANGE_SECS.contains(&secs) => Ok(secs),
+                _ => Err(anyhow!("{}=\"{}\" must be a number of seconds between {} and {}",
+                                 name, value, PollIntervals::RANGE_SECS.start(), PollIntervals::RANGE_SECS.end())),
+            }
+        };
+
+        Ok(PollIntervals {
+            driving_secs: secs("DRIVING", defaults.driving_secs)?,
+            fast_charging_secs: secs("FAST_CHARGING", defaults.fast_charging_secs)?,
+            ac_charging_secs: secs("AC_CHARGING", defaults.ac_charging_secs)?,
+            parked_secs: secs("PARKED", defaults.parked_secs)?,
+            unknown_secs: secs("UNKNOWN", defaults.unknown_secs)?,
+        })
+    }
+}
+
+impl Default for PollIntervals {
+    fn default() -> Self {
+        PollIntervals {
+            driving_secs: POLL_DRIVING_SECS,
+            fast_charging_secs: POLL_FAST_CHARGING_SECS,
+            ac_charging_secs: POLL_CHARGING_SECS,
+            parked_secs: POLL_PARKED_SECS,
+            unknown_secs: POLL_UNKNOWN_SECS,
+        }
+    }
+}
+
+/// Which vehicles of the account to poll, matched by display name or VIN, ignoring case.
+///
+/// An empty allowlist allows every vehicle, the denylist takes precedence over the allowlist.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VehicleFilter {
+    pub allowlist: Vec<String>,
+    pub denylist: Vec<String>,
+}
+
+impl VehicleFilter {
+    /// Reads the comma separated `TESLA_VEHICLE_ALLOWLIST` and `TESLA_VEHICLE_DENYLIST`.
+    pub fn from_env() -> Self {
+        let list = |name: &str| -> Vec<String> {
+            env::var(name)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        VehicleFilter { allowlist: list("TESLA_VEHICLE_ALLOWLIST"), denylist: list("TESLA_VEHICLE_DENYLIST") }
+    }
+
+    pub fn allows(&self, vehicle: &Vehicle) -> bool {
+        let matches = |entry: &String| {
+            entry.eq_ignore_ascii_case(&vehicle.display_name) || (!vehicle.vin.is_empty() && entry.eq_ignore_ascii_case(&vehicle.vin))
+        };
+        if self.denylist.iter().any(matches) {
+            return false;
+        }
+        self.allowlist.is_empty() || self.allowlist.iter().any(matches)
+    }
+}
+
+pub(crate) fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|value| value.eq_ignore_ascii_case("true") || value == "1")
+        .unwrap_or(false)
+}
 
-use crate::tesla_api_client::{TeslaApiClient};
-use crate::tesla_api_client::dtos::{AuthToken, VehicleData};
+fn env_secs(name: &str, default: u64) -> u64 {
+    match env::var(name) {
+        Err(_) => default,
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            warn!("Ignoring invalid {}=\"{}\", using {} seconds", name, value, default);
+            default
+        }),
+    }
+}
+
+fn env_pct(name: &str, default: i32) -> i32 {
+    match env::var(name) {
+        Err(_) => default,
+        Ok(value) => match value.trim().parse() {
+            Ok(pct) if (0..=100).contains(&pct) => pct,
+            _ => {
+                warn!("Ignoring invalid {}=\"{}\", using {}%", name, value, default);
+                default
+            }
+        },
+    }
+}
+
+static OUTPUTS: Lazy<RwLock<Vec<Box<dyn Output>>>> = Lazy::new(|| RwLock::new(output::from_env()));
+
+const POLL_DRIVING_SECS: u64 = 5;
+const POLL_FAST_CHARGING_SECS: u64 = 5;
+const POLL_CHARGING_SECS: u64 = 15;
+const POLL_PARKED_SECS: u64 = 30;
+const POLL_UNKNOWN_SECS: u64 = 30;
+const POLL_ENERGY_SITE_SECS: u64 = 30;
+
+const KM_PER_MILE: f64 = 1.60934;
+
+static CONFIG_INFO_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_config_info", "Exporter configuration"),
+                     &["version", "poll_driving_secs", "poll_charging_secs", "poll_parked_secs", "distance_unit", "temperature_unit"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static BUILD_INFO_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_build_info", "Exporter build information"),
+                     &["version", "git_commit", "build_timestamp"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static VEHICLE_DATA_TIMESTAMP_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_vehicle_data_timestamp_seconds", "When the vehicle reported the recorded data (seconds since the epoch)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
 
 static BATTERY_LEVEL_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
-    IntGaugeVec::new(opts!("tesla_charge_state_battery_level", "Battery Level (%)"), &["car_name"])
+    IntGaugeVec::new(metric_opts("tesla_charge_state_battery_level", "Battery Level (%)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static USABLE_BATTERY_LEVEL_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_charge_state_usable_battery_level", "Usable Battery Level (%)"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static BATTERY_RANGE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_charge_state_battery_range", "Battery Range (Miles)"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_charge_state_battery_range", &format!("Battery Range ({})", UNITS.distance.name())), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static BATTERY_EST_RANGE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_charge_state_est_battery_range", "Estimated Battery Range (Miles)"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_charge_state_est_battery_range", &format!("Estimated Battery Range ({})", UNITS.distance.name())), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static BATTERY_IDEAL_RANGE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_charge_state_ideal_battery_range", "Ideal Battery Range (Miles)"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_charge_state_ideal_battery_range", &format!("Ideal Battery Range ({})", UNITS.distance.name())), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static CHARGE_LIMIT_SOC_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_charge_state_charge_limit_soc", "Charge Limit (%)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static CHARGE_LIMIT_SOC_STD_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_charge_state_charge_limit_soc_std", "Standard Charge Limit (%)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static CHARGE_LEVEL_HEADROOM_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_charge_level_headroom", "Charge Limit minus Battery Level (%)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static HISTORICAL_CHARGE_ENERGY_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_historical_charge_energy_total", "Energy added by past charging sessions (kWh)"), &["car_name", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static CHARGING_STATE_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_charging_state", "Charging State"), &["car_name", "state", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static CHARGE_ENERGY_ADDED_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_charge_state_charge_energy_added_kwh", "Energy added during the current charging session (kWh)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static CHARGE_MILES_ADDED_RATED_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_charge_state_charge_miles_added_rated", "Rated range added during the current charging session (mi)"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
+static CHARGE_SESSIONS_ENERGY_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_charge_sessions_energy_kwh_total", "Energy added by charging sessions since the exporter started (kWh)"), &["car_name", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static SENTRY_MODE_ACTIVE_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_sentry_mode_active_seconds_total", "Time sentry mode was active since the exporter started (seconds)"), &["car_name", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
 static CHARGE_RATE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_charge_state_charge_rate", "Battery Charge Rate"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_charge_state_charge_rate_mi_per_hour", "Battery Charge Rate (mi/hr)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static CHARGE_RATE_KM_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_charge_state_charge_rate_km_per_hour", "Battery Charge Rate (km/hr)"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static TIME_TO_FULL_CHARGE_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
-    IntGaugeVec::new(opts!("tesla_charge_state_minutes_to_full_charge", "Time to Full Charge"), &["car_name"])
+    IntGaugeVec::new(metric_opts("tesla_charge_state_minutes_to_full_charge", "Time to Full Charge"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static CHARGER_VOLTAGE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_charge_state_charger_voltage", "Charger Voltage"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_charge_state_charger_voltage", "Charger Voltage"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
-static CHARGER_POWER_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_charge_state_charger_power", "Charger Power"), &["car_name"])
+static CHARGER_POWER_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_charge_state_charger_power_kw", "Charger Power (kW) as reported by the API"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static CALCULATED_CHARGER_POWER_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_charge_state_calculated_charger_power_kw", "Charger Power (kW) from voltage x current x phases"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static CHARGER_ACTUAL_CURRENT_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_charge_state_charger_actual_current", "Charger Actual Current"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_charge_state_charger_actual_current", "Charger Actual Current"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static SPEED_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_drive_state_speed", "Vehicle speed (MPH)"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_drive_state_speed", &format!("Vehicle speed ({})", UNITS.distance.speed_name())), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static POWER_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_drive_state_power", "Vehicle Power"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_drive_state_power", "Vehicle Power"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static NET_POWER_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_vehicle_net_power_kw", "Power balance (kW), positive while charging, negative while driving"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static ODOMETER_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_vehicle_state_odometer", "Vehicle odometer (Miles)"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_vehicle_state_odometer", &format!("Vehicle odometer ({})", UNITS.distance.name())), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static SOFTWARE_UPDATE_DURATION_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_vehicle_state_software_update_expected_duration_sec", "Expected installation time of the pending software update (Seconds)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static HOMELINK_NEARBY_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_vehicle_state_homelink_nearby", "Is a HomeLink device nearby"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static LOCKED_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_vehicle_state_locked", "Is the vehicle locked"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static SENTRY_MODE_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_vehicle_state_sentry_mode", "Is sentry mode on"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static USER_PRESENT_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_vehicle_state_is_user_present", "Is a user present in the vehicle"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static VALET_MODE_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_vehicle_state_valet_mode", "Is valet mode on"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static TPMS_PRESSURE_FL_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_vehicle_state_tpms_pressure_fl", "Tire pressure front left (bar)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static TPMS_PRESSURE_FR_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_vehicle_state_tpms_pressure_fr", "Tire pressure front right (bar)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static TPMS_PRESSURE_RL_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_vehicle_state_tpms_pressure_rl", "Tire pressure rear left (bar)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static TPMS_PRESSURE_RR_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_vehicle_state_tpms_pressure_rr", "Tire pressure rear right (bar)"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static INSIDE_TEMPERATURE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_climate_state_inside_temp", "Inside Temperature (DegC)"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_climate_state_inside_temp", &format!("Inside Temperature ({})", UNITS.temperature.name())), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static OUTSIDE_TEMPERATURE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_climate_state_outside_temp", "Outside Temperature (DegC)"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_climate_state_outside_temp", &format!("Outside Temperature ({})", UNITS.temperature.name())), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static DRIVER_TEMPERATURE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_climate_state_driver_temp_setting", "Driver's Temperature Setting (DegC)"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_climate_state_driver_temp_setting", &format!("Driver's Temperature Setting ({})", UNITS.temperature.name())), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static PASSENGER_TEMPERATURE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_climate_state_passenger_temp_setting", "Passenger's Temperature Setting (DegC)"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_climate_state_passenger_temp_setting", &format!("Passenger's Temperature Setting ({})", UNITS.temperature.name())), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static GEO_LAT_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_drive_state_latitude", "Vehicle Latitude"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_drive_state_latitude", "Vehicle Latitude"), &["car_name", "native_type", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static GEO_LONG_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_drive_state_longitude", "Vehicle Longitude"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_drive_state_longitude", "Vehicle Longitude"), &["car_name", "native_type", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static GEO_LAT_WGS84_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_drive_state_latitude_wgs84", "Vehicle Latitude (WGS-84)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static GEO_LONG_WGS84_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_drive_state_longitude_wgs84", "Vehicle Longitude (WGS-84)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static GEO_NATIVE_LAT_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_drive_state_native_latitude", "Vehicle Latitude in its native coordinate system"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static GEO_NATIVE_LONG_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_drive_state_native_longitude", "Vehicle Longitude in its native coordinate system"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static GEO_HEADING_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
-    GaugeVec::new(opts!("tesla_drive_state_heading", "Vehicle Heading"), &["car_name"])
+    GaugeVec::new(metric_opts("tesla_drive_state_heading", "Vehicle Heading"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static CAR_STATE_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
-    IntGaugeVec::new(opts!("tesla_car_state", "Car State"), &["car_name"])
+    IntGaugeVec::new(metric_opts("tesla_car_state", "Car State"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
 static CAR_ONLINE_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
-    IntGaugeVec::new(opts!("tesla_is_online", "Is vehicle online"), &["car_name"])
+    IntGaugeVec::new(metric_opts("tesla_is_online", "Is vehicle online"), &["car_name", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
-static SHIFT_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
-    IntGaugeVec::new(opts!("tesla_drive_state_shift_state", "Vehicle Shift State"), &["car_name"])
+static VEHICLE_INFO_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_vehicle_info", "Vehicle information"), &["car_name", "vin", "color", "account_index"])
         .expect("Could not create lazy GaugeVec")
 });
 
-fn register() -> PrometheusMetrics {
+static CALENDAR_ENABLED_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_vehicle_calendar_enabled", "Is calendar sync enabled"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static SHIFT_VALUE_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_drive_state_shift_state_value", "Vehicle Shift State (R=-1, P=0, N=1, D=2, unknown=-2)"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static SHIFT_INFO_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(metric_opts("tesla_drive_state_shift_state_info", "Vehicle Shift State"), &["car_name", "shift_state", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static API_CALLS_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_api_calls_total", "Tesla API requests made for the vehicle"), &["car_name", "endpoint", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static API_CALL_RATE_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_api_calls_per_vehicle_per_hour", "Tesla API requests made for the vehicle over the last hour"), &["car_name", "account_index"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static POLL_ATTEMPTS_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_poll_attempts_total", "Vehicle polls attempted"), &["car_name", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static POLL_SUCCESSES_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_poll_successes_total", "Vehicle polls that fetched and recorded vehicle data"), &["car_name", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static API_ERRORS_COUNTER: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(metric_opts("tesla_api_errors_total", "Failed Tesla API requests by error type"), &["car_name", "error_type", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static WAKE_ATTEMPTS_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_wake_attempts_total", "Wake up requests sent to the vehicle"), &["car_name", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static WAKE_SUCCESS_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_wake_success_total", "Successful vehicle wake ups"), &["car_name", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static WAKE_DURATION_HISTOGRAM: Lazy<HistogramVec> = Lazy::new(|| {
+    let opts = metric_opts("tesla_wake_duration_seconds", "Time from the first wake up request until the vehicle is online");
+    HistogramVec::new(HistogramOpts::from(opts).buckets(vec![1.0, 5.0, 10.0, 15.0, 20.0, 30.0, 45.0, 60.0]), &["car_name", "account_index"])
+        .expect("Could not create lazy HistogramVec")
+});
+
+#[cfg(target_os = "linux")]
+static POLLING_THREAD_CPU_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_polling_thread_cpu_seconds_total", "CPU time used by the vehicle's polling thread"), &["car_name", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static STALE_RESPONSE_COUNTER: Lazy<CounterVec> = Lazy::new(|| {
+    CounterVec::new(metric_opts("tesla_stale_response_total", "Polls that returned the same vehicle data timestamp as the polls before"), &["car_name", "account_index"])
+        .expect("Could not create lazy CounterVec")
+});
+
+static ENERGY_BATTERY_POWER_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_energy_battery_power", "Energy Site Battery Power, positive while discharging (W)"), &["site_name"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static ENERGY_SOLAR_POWER_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_energy_solar_power", "Energy Site Solar Power (W)"), &["site_name"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static ENERGY_GRID_POWER_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_energy_grid_power", "Energy Site Grid Power, positive while importing (W)"), &["site_name"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+static ENERGY_PERCENTAGE_CHARGED_GAUGE: Lazy<GaugeVec> = Lazy::new(|| {
+    GaugeVec::new(metric_opts("tesla_energy_percentage_charged", "Energy Site Battery Level (%)"), &["site_name"])
+        .expect("Could not create lazy GaugeVec")
+});
+
+/// A `car_name` gauge recorded from the vehicle data.
+pub(crate) enum VehicleDataGauge {
+    Int(&'static Lazy<IntGaugeVec>),
+    Float(&'static Lazy<GaugeVec>),
+    /// Also labeled with the `native_type` of the coordinates.
+    NativeType(&'static Lazy<GaugeVec>),
+}
+
+/// A value of the vehicle data, `name` holds the segments of its metric name after the `tesla`
+/// prefix.
+pub(crate) struct VehicleDataField {
+    pub(crate) name: &'static [&'static str],
+    pub(crate) gauge: VehicleDataGauge,
+    pub(crate) value: fn(&VehicleData) -> f64,
+}
+
+/// The values `record()` sets gauges from and the outputs publish, see [`output::samples`].
+/// Distances and temperatures are converted to `UNITS`.
+pub(crate) static VEHICLE_DATA_FIELDS: &[VehicleDataField] = &[
+    VehicleDataField {
+        name: &["charge_state", "battery_level"],
+        gauge: VehicleDataGauge::Int(&BATTERY_LEVEL_GAUGE),
+        value: |v| f64::from(v.charge_state.battery_level),
+    },
+    VehicleDataField {
+        name: &["charge_state", "usable_battery_level"],
+        gauge: VehicleDataGauge::Int(&USABLE_BATTERY_LEVEL_GAUGE),
+        value: |v| f64::from(v.charge_state.usable_battery_level),
+    },
+    VehicleDataField {
+        name: &["charge_state", "battery_range"],
+        gauge: VehicleDataGauge::Float(&BATTERY_RANGE_GAUGE),
+        value: |v| UNITS.distance.from_miles(v.charge_state.battery_range),
+    },
+    VehicleDataField {
+        name: &["charge_state", "est_battery_range"],
+        gauge: VehicleDataGauge::Float(&BATTERY_EST_RANGE_GAUGE),
+        value: |v| UNITS.distance.from_miles(v.charge_state.est_battery_range),
+    },
+    VehicleDataField {
+        name: &["charge_state", "ideal_battery_range"],
+        gauge: VehicleDataGauge::Float(&BATTERY_IDEAL_RANGE_GAUGE),
+        value: |v| UNITS.distance.from_miles(v.charge_state.ideal_battery_range),
+    },
+    VehicleDataField {
+        name: &["charge_state", "minutes_to_full_charge"],
+        gauge: VehicleDataGauge::Int(&TIME_TO_FULL_CHARGE_GAUGE),
+        value: |v| v.charge_state.minutes_to_full_charge as f64,
+    },
+    VehicleDataField {
+        name: &["charge_state", "charge_rate_mi_per_hour"],
+        gauge: VehicleDataGauge::Float(&CHARGE_RATE_GAUGE),
+        value: |v| v.charge_state.charge_rate,
+    },
+    VehicleDataField {
+        name: &["charge_state", "charge_rate_km_per_hour"],
+        gauge: VehicleDataGauge::Float(&CHARGE_RATE_KM_GAUGE),
+        value: |v| v.charge_state.charge_rate * KM_PER_MILE,
+    },
+    VehicleDataField {
+        name: &["charge_state", "charge_energy_added_kwh"],
+        gauge: VehicleDataGauge::Float(&CHARGE_ENERGY_ADDED_GAUGE),
+        value: |v| v.charge_state.charge_energy_added,
+    },
+    VehicleDataField {
+        name: &["charge_state", "charge_miles_added_rated"],
+        gauge: VehicleDataGauge::Float(&CHARGE_MILES_ADDED_RATED_GAUGE),
+        value: |v| v.charge_state.charge_miles_added_rated,
+    },
+    VehicleDataField {
+        name: &["charge_state", "charge_limit_soc"],
+        gauge: VehicleDataGauge::Int(&CHARGE_LIMIT_SOC_GAUGE),
+        value: |v| f64::from(v.charge_state.charge_limit_soc),
+    },
+    VehicleDataField {
+        name: &["charge_state", "charge_limit_soc_std"],
+        gauge: VehicleDataGauge::Int(&CHARGE_LIMIT_SOC_STD_GAUGE),
+        value: |v| f64::from(v.charge_state.charge_limit_soc_std),
+    },
+    VehicleDataField {
+        name: &["charge_level_headroom"],
+        gauge: VehicleDataGauge::Int(&CHARGE_LEVEL_HEADROOM_GAUGE),
+        value: |v| f64::from(v.charge_state.charge_limit_soc - v.charge_state.battery_level),
+    },
+    VehicleDataField {
+        name: &["charge_state", "charger_voltage"],
+        gauge: VehicleDataGauge::Float(&CHARGER_VOLTAGE_GAUGE),
+        value: |v| v.charge_state.charger_voltage,
+    },
+    VehicleDataField {
+        name: &["charge_state", "charger_power_kw"],
+        gauge: VehicleDataGauge::Int(&CHARGER_POWER_GAUGE),
+        value: |v| v.charge_state.charger_power,
+    },
+    VehicleDataField {
+        name: &["charge_state", "calculated_charger_power_kw"],
+        gauge: VehicleDataGauge::Float(&CALCULATED_CHARGER_POWER_GAUGE),
+        value: |v| v.charge_state.calculated_charger_power_kw(),
+    },
+    VehicleDataField {
+        name: &["charge_state", "charger_actual_current"],
+        gauge: VehicleDataGauge::Float(&CHARGER_ACTUAL_CURRENT_GAUGE),
+        value: |v| v.charge_state.charger_actual_current,
+    },
+    VehicleDataField {
+        name: &["drive_state", "speed"],
+        gauge: VehicleDataGauge::Float(&SPEED_GAUGE),
+        value: |v| UNITS.distance.from_miles(v.drive_state.speed.unwrap_or(0.0_f64)),
+    },
+    VehicleDataField {
+        name: &["drive_state", "power"],
+        gauge: VehicleDataGauge::Float(&POWER_GAUGE),
+        value: |v| v.drive_state.power,
+    },
+    VehicleDataField {
+        name: &["net_power_kw"],
+        gauge: VehicleDataGauge::Float(&NET_POWER_GAUGE),
+        value: |v| v.net_power_kw(),
+    },
+    VehicleDataField {
+        name: &["drive_state", "latitude"],
+        gauge: VehicleDataGauge::NativeType(&GEO_LAT_GAUGE),
+        value: |v| v.drive_state.latitude,
+    },
+    VehicleDataField {
+        name: &["drive_state", "longitude"],
+        gauge: VehicleDataGauge::NativeType(&GEO_LONG_GAUGE),
+        value: |v| v.drive_state.longitude,
+    },
+    VehicleDataField {
+        name: &["drive_state", "heading"],
+        gauge: VehicleDataGauge::Float(&GEO_HEADING_GAUGE),
+        value: |v| v.drive_state.heading,
+    },
+    VehicleDataField {
+        name: &["drive_state", "shift_state_value"],
+        gauge: VehicleDataGauge::Int(&SHIFT_VALUE_GAUGE),
+        value: |v| v.drive_state.shift_state_value() as f64,
+    },
+    VehicleDataField {
+        name: &["vehicle_state", "odometer"],
+        gauge: VehicleDataGauge::Float(&ODOMETER_GAUGE),
+        value: |v| UNITS.distance.from_miles(v.vehicle_state.odometer),
+    },
+    VehicleDataField {
+        name: &["vehicle_state", "locked"],
+        gauge: VehicleDataGauge::Int(&LOCKED_GAUGE),
+        value: |v| f64::from(u8::from(v.vehicle_state.locked)),
+    },
+    VehicleDataField {
+        name: &["vehicle_state", "is_user_present"],
+        gauge: VehicleDataGauge::Int(&USER_PRESENT_GAUGE),
+        value: |v| f64::from(u8::from(v.vehicle_state.is_user_present)),
+    },
+    VehicleDataField {
+        name: &["vehicle_state", "valet_mode"],
+        gauge: VehicleDataGauge::Int(&VALET_MODE_GAUGE),
+        value: |v| f64::from(u8::from(v.vehicle_state.valet_mode)),
+    },
+    VehicleDataField {
+        name: &["vehicle_state", "software_update_expected_duration_sec"],
+        gauge: VehicleDataGauge::Int(&SOFTWARE_UPDATE_DURATION_GAUGE),
+        value: |v| v.vehicle_state.software_update.as_ref().map_or(0.0, |update| f64::from(update.expected_duration_sec)),
+    },
+    VehicleDataField {
+        name: &["climate_state", "inside_temp"],
+        gauge: VehicleDataGauge::Float(&INSIDE_TEMPERATURE_GAUGE),
+        value: |v| UNITS.temperature.from_celsius(v.climate_state.inside_temp),
+    },
+    VehicleDataField {
+        name: &["climate_state", "outside_temp"],
+        gauge: VehicleDataGauge::Float(&OUTSIDE_TEMPERATURE_GAUGE),
+        value: |v| UNITS.temperature.from_celsius(v.climate_state.outside_temp),
+    },
+    VehicleDataField {
+        name: &["climate_state", "driver_temp_setting"],
+        gauge: VehicleDataGauge::Float(&DRIVER_TEMPERATURE_GAUGE),
+        value: |v| UNITS.temperature.from_celsius(v.climate_state.driver_temp_setting),
+    },
+    VehicleDataField {
+        name: &["climate_state", "passenger_temp_setting"],
+        gauge: VehicleDataGauge::Float(&PASSENGER_TEMPERATURE_GAUGE),
+        value: |v| UNITS.temperature.from_celsius(v.climate_state.passenger_temp_setting),
+    },
+];
+
+/// Gauges for `VEHICLE_DATA_EXTRA_FIELDS`, a comma-separated list of paths to fields the DTOs
+/// don't map yet, e.g. `charge_state.some_new_field` is exported as
+/// `tesla_extra_charge_state_some_new_field`. Paths may point into nested objects.
+static EXTRA_FIELD_GAUGES: Lazy<Vec<(String, GaugeVec)>> = Lazy::new(|| {
+    env::var("VEHICLE_DATA_EXTRA_FIELDS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .filter_map(|path| {
+            let name = format!("tesla_extra_{}", path.replace('.', "_"));
+            match GaugeVec::new(metric_opts(&name, &format!("Extra vehicle data field {}", path)), &["car_name", "account_index"]) {
+                Ok(gauge) => Some((path.to_string(), gauge)),
+                Err(err) => {
+                    warn!("Ignoring invalid extra field: \"{}\" error=\"{}\"", path, err);
+                    None
+                }
+            }
+        })
+        .collect()
+});
+
+/// Registers the exporter metrics with a new `PrometheusMetrics`, which serves them on `/metrics`.
+pub fn register() -> PrometheusMetrics {
     let prometheus = PrometheusMetrics::new();
 
+    prometheus
+        .registry()
+        .register(Box::new(CONFIG_INFO_GAUGE.clone()))
+        .unwrap();
+
+    let poll_intervals = PollIntervals::from_env().unwrap_or_default();
+    CONFIG_INFO_GAUGE
+        .with_label_values(&[
+            build_info::VERSION,
+            &poll_intervals.driving_secs.to_string(),
+            &poll_intervals.ac_charging_secs.to_string(),
+            &poll_intervals.parked_secs.to_string(),
+            UNITS.distance.label(),
+            UNITS.temperature.label(),
+        ])
+        .set(1);
+
+    prometheus
+        .registry()
+        .register(Box::new(BUILD_INFO_GAUGE.clone()))
+        .unwrap();
+
+    BUILD_INFO_GAUGE
+        .with_label_values(&[build_info::VERSION, build_info::GIT_HASH, build_info::BUILD_TIMESTAMP])
+        .set(1);
+
+    prometheus
+        .registry()
+        .register(Box::new(VEHICLE_DATA_TIMESTAMP_GAUGE.clone()))
+        .unwrap();
+
     prometheus
         .registry()
         .register(Box::new(BATTERY_LEVEL_GAUGE.clone()))
         .unwrap();
 
+    prometheus
+        .registry()
+        .register(Box::new(USABLE_BATTERY_LEVEL_GAUGE.clone()))
+        .unwrap();
+
     prometheus
         .registry()
         .register(Box::new(BATTERY_RANGE_GAUGE.clone()))
@@ -163,6 +867,11 @@ fn register() -> PrometheusMetrics {
         .register(Box::new(CHARGER_POWER_GAUGE.clone()))
         .unwrap();
 
+    prometheus
+        .registry()
+        .register(Box::new(CALCULATED_CHARGER_POWER_GAUGE.clone()))
+        .unwrap();
+
     prometheus
         .registry()
         .register(Box::new(CHARGER_ACTUAL_CURRENT_GAUGE.clone()))
@@ -175,168 +884,482 @@ fn register() -> PrometheusMetrics {
 
     prometheus
         .registry()
-        .register(Box::new(CHARGE_RATE_GAUGE.clone()))
+        .register(Box::new(CHARGE_ENERGY_ADDED_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(SPEED_GAUGE.clone()))
+        .register(Box::new(CHARGE_MILES_ADDED_RATED_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(POWER_GAUGE.clone()))
+        .register(Box::new(CHARGE_SESSIONS_ENERGY_COUNTER.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(ODOMETER_GAUGE.clone()))
+        .register(Box::new(SENTRY_MODE_ACTIVE_COUNTER.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(INSIDE_TEMPERATURE_GAUGE.clone()))
+        .register(Box::new(CHARGE_RATE_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(OUTSIDE_TEMPERATURE_GAUGE.clone()))
+        .register(Box::new(CHARGE_RATE_KM_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(DRIVER_TEMPERATURE_GAUGE.clone()))
+        .register(Box::new(CHARGE_LIMIT_SOC_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(PASSENGER_TEMPERATURE_GAUGE.clone()))
+        .register(Box::new(CHARGE_LIMIT_SOC_STD_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(GEO_LAT_GAUGE.clone()))
+        .register(Box::new(CHARGE_LEVEL_HEADROOM_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(GEO_LONG_GAUGE.clone()))
+        .register(Box::new(HISTORICAL_CHARGE_ENERGY_COUNTER.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(GEO_HEADING_GAUGE.clone()))
+        .register(Box::new(CHARGING_STATE_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(CAR_STATE_GAUGE.clone()))
+        .register(Box::new(SPEED_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(CAR_ONLINE_GAUGE.clone()))
+        .register(Box::new(POWER_GAUGE.clone()))
         .unwrap();
 
     prometheus
         .registry()
-        .register(Box::new(SHIFT_GAUGE.clone()))
+        .register(Box::new(NET_POWER_GAUGE.clone()))
         .unwrap();
 
     prometheus
-}
-
-fn record(vehicle_data: &VehicleData) -> CarState {
-    let car_state = CarState::from(vehicle_data.clone());
+        .registry()
+        .register(Box::new(ODOMETER_GAUGE.clone()))
+        .unwrap();
 
-    BATTERY_LEVEL_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(i64::from(vehicle_data.charge_state.battery_level));
+    prometheus
+        .registry()
+        .register(Box::new(SOFTWARE_UPDATE_DURATION_GAUGE.clone()))
+        .unwrap();
 
-    BATTERY_RANGE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.charge_state.battery_range);
+    prometheus
+        .registry()
+        .register(Box::new(HOMELINK_NEARBY_GAUGE.clone()))
+        .unwrap();
 
-    BATTERY_EST_RANGE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.charge_state.est_battery_range);
+    prometheus
+        .registry()
+        .register(Box::new(LOCKED_GAUGE.clone()))
+        .unwrap();
 
-    BATTERY_IDEAL_RANGE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.charge_state.ideal_battery_range);
+    prometheus
+        .registry()
+        .register(Box::new(SENTRY_MODE_GAUGE.clone()))
+        .unwrap();
 
-    TIME_TO_FULL_CHARGE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.charge_state.minutes_to_full_charge);
+    prometheus
+        .registry()
+        .register(Box::new(USER_PRESENT_GAUGE.clone()))
+        .unwrap();
 
-    CHARGE_RATE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.charge_state.charge_rate);
+    prometheus
+        .registry()
+        .register(Box::new(VALET_MODE_GAUGE.clone()))
+        .unwrap();
 
-    CHARGER_VOLTAGE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.charge_state.charger_voltage);
+    prometheus
+        .registry()
+        .register(Box::new(TPMS_PRESSURE_FL_GAUGE.clone()))
+        .unwrap();
 
-    CHARGER_POWER_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.charge_state.charger_power);
+    prometheus
+        .registry()
+        .register(Box::new(TPMS_PRESSURE_FR_GAUGE.clone()))
+        .unwrap();
 
-    CHARGER_ACTUAL_CURRENT_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.charge_state.charger_actual_current);
+    prometheus
+        .registry()
+        .register(Box::new(TPMS_PRESSURE_RL_GAUGE.clone()))
+        .unwrap();
 
-    SPEED_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.drive_state.speed.unwrap_or(0.0_f64));
+    prometheus
+        .registry()
+        .register(Box::new(TPMS_PRESSURE_RR_GAUGE.clone()))
+        .unwrap();
 
-    POWER_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.drive_state.power);
+    prometheus
+        .registry()
+        .register(Box::new(INSIDE_TEMPERATURE_GAUGE.clone()))
+        .unwrap();
 
-    ODOMETER_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.vehicle_state.odometer);
+    prometheus
+        .registry()
+        .register(Box::new(OUTSIDE_TEMPERATURE_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(DRIVER_TEMPERATURE_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(PASSENGER_TEMPERATURE_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(GEO_LAT_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(GEO_LONG_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(GEO_LAT_WGS84_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(GEO_LONG_WGS84_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(GEO_NATIVE_LAT_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(GEO_NATIVE_LONG_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(GEO_HEADING_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(CAR_STATE_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(CAR_ONLINE_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(CALENDAR_ENABLED_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(VEHICLE_INFO_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(SHIFT_VALUE_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(SHIFT_INFO_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(POLL_ATTEMPTS_COUNTER.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(POLL_SUCCESSES_COUNTER.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(API_ERRORS_COUNTER.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(WAKE_ATTEMPTS_COUNTER.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(API_CALLS_COUNTER.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(API_CALL_RATE_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(WAKE_SUCCESS_COUNTER.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(WAKE_DURATION_HISTOGRAM.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(API_CALL_DURATION_HISTOGRAM.clone()))
+        .unwrap();
+
+    #[cfg(target_os = "linux")]
+    prometheus
+        .registry()
+        .register(Box::new(POLLING_THREAD_CPU_COUNTER.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(STALE_RESPONSE_COUNTER.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(ENERGY_BATTERY_POWER_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(ENERGY_SOLAR_POWER_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(ENERGY_GRID_POWER_GAUGE.clone()))
+        .unwrap();
+
+    prometheus
+        .registry()
+        .register(Box::new(ENERGY_PERCENTAGE_CHARGED_GAUGE.clone()))
+        .unwrap();
+
+    for (path, gauge) in EXTRA_FIELD_GAUGES.iter() {
+        if let Err(err) = prometheus.registry().register(Box::new(gauge.clone())) {
+            warn!("Failed to register extra field: \"{}\" error=\"{}\"", path, err);
+        }
+    }
+
+    prometheus
+}
+
+fn record(vehicle_data: &VehicleData, account_index: &str) -> CarState {
+    let car_state = CarState::from(vehicle_data.clone());
+    let labels = [vehicle_data.display_name.as_str(), account_index];
+
+    VEHICLE_DATA_TIMESTAMP_GAUGE
+        .with_label_values(&labels)
+        .set(vehicle_data.charge_state.timestamp / 1000);
+
+    for field in VEHICLE_DATA_FIELDS {
+        let value = (field.value)(vehicle_data);
+        match field.gauge {
+            VehicleDataGauge::Int(gauge) => gauge.with_label_values(&labels).set(value.round() as i64),
+            VehicleDataGauge::Float(gauge) => gauge.with_label_values(&labels).set(value),
+            VehicleDataGauge::NativeType(gauge) => gauge
+                .with_label_values(&[&vehicle_data.display_name, vehicle_data.drive_state.native_type_label(), account_index])
+                .set(value),
+        }
+    }
+
+    set_state_label(&CHARGING_STATE_GAUGE, &vehicle_data.display_name, account_index,
+                    VehicleChargeState::CHARGING_STATE_LABELS, vehicle_data.charge_state.charging_state_label());
 
-    INSIDE_TEMPERATURE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.climate_state.inside_temp);
+    let features = vehicle_data.vehicle_state.api_version_supported_features();
 
-    OUTSIDE_TEMPERATURE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.climate_state.outside_temp);
+    if let (true, Some(homelink_nearby)) = (features.supports_homelink, vehicle_data.vehicle_state.homelink_nearby) {
+        HOMELINK_NEARBY_GAUGE
+            .with_label_values(&labels)
+            .set(if homelink_nearby { 1 } else { 0 });
+    }
 
-    DRIVER_TEMPERATURE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.climate_state.driver_temp_setting);
+    if features.supports_sentry_mode {
+        SENTRY_MODE_GAUGE
+            .with_label_values(&labels)
+            .set(if vehicle_data.vehicle_state.sentry_mode { 1 } else { 0 });
+    }
 
-    PASSENGER_TEMPERATURE_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.climate_state.passenger_temp_setting);
+    for (gauge, pressure) in [
+        (&TPMS_PRESSURE_FL_GAUGE, vehicle_data.vehicle_state.tpms_pressure_fl),
+        (&TPMS_PRESSURE_FR_GAUGE, vehicle_data.vehicle_state.tpms_pressure_fr),
+        (&TPMS_PRESSURE_RL_GAUGE, vehicle_data.vehicle_state.tpms_pressure_rl),
+        (&TPMS_PRESSURE_RR_GAUGE, vehicle_data.vehicle_state.tpms_pressure_rr),
+    ] {
+        if let Some(pressure) = pressure {
+            gauge
+                .with_label_values(&labels)
+                .set(pressure);
+        }
+    }
 
-    GEO_LAT_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
+    GEO_LAT_WGS84_GAUGE
+        .with_label_values(&labels)
         .set(vehicle_data.drive_state.latitude);
 
-    GEO_LONG_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
+    GEO_LONG_WGS84_GAUGE
+        .with_label_values(&labels)
         .set(vehicle_data.drive_state.longitude);
 
-    GEO_HEADING_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.drive_state.heading);
+    match vehicle_data.drive_state.native_coordinates_if_different() {
+        Some((native_latitude, native_longitude)) => {
+            GEO_NATIVE_LAT_GAUGE
+                .with_label_values(&labels)
+                .set(native_latitude);
+            GEO_NATIVE_LONG_GAUGE
+                .with_label_values(&labels)
+                .set(native_longitude);
+        }
+        None => {
+            // Same as the WGS-84 pair, so drop the series instead of exporting duplicates
+            let _ = GEO_NATIVE_LAT_GAUGE.remove_label_values(&labels);
+            let _ = GEO_NATIVE_LONG_GAUGE.remove_label_values(&labels);
+        }
+    }
+
+    set_state_label(&SHIFT_INFO_GAUGE, &vehicle_data.display_name, account_index,
+                    VehicleDriveState::SHIFT_STATE_LABELS, vehicle_data.drive_state.shift_state_label());
+
+    if !EXTRA_FIELD_GAUGES.is_empty() {
+        let extra_values = vehicle_data.extra_values();
+        for (path, gauge) in EXTRA_FIELD_GAUGES.iter() {
+            if let Some((_, value)) = extra_values.iter().find(|(extra_path, _)| extra_path == path) {
+                gauge.with_label_values(&labels).set(*value);
+            }
+        }
+    }
 
-    SHIFT_GAUGE
-        .with_label_values(&[&vehicle_data.display_name])
-        .set(vehicle_data.drive_state.shift_state_value());
+    car_state
+}
 
+fn record_and_publish(vehicle_data: &VehicleData, account_index: &str) -> CarState {
+    let car_state = record(vehicle_data, account_index);
+    TIME_SERIES_STORE.record(vehicle_data.id, MetricSnapshot::from(vehicle_data));
+    publish(vehicle_data);
     car_state
 }
 
+/// Sends recorded vehicle data to the outputs configured next to Prometheus, e.g. StatsD.
+fn publish(vehicle_data: &VehicleData) {
+    for output in OUTPUTS.read().expect("Outputs lock is poisoned").iter() {
+        if let Err(err) = output.publish(vehicle_data) {
+            warn!("Failed to publish vehicle data: Vehicle=\"{}\" error=\"{:#}\"", vehicle_data.display_name, err);
+        }
+    }
+}
+
+fn publish_car_state(car_name: &str, previous_state: &CarState, car_state: &CarState) {
+    for output in OUTPUTS.read().expect("Outputs lock is poisoned").iter() {
+        if let Err(err) = output.publish_car_state(car_name, previous_state, car_state) {
+            warn!("Failed to publish car state: Vehicle=\"{}\" CarState=\"{}\" error=\"{:#}\"", car_name, car_state, err);
+        }
+    }
+}
+
+/// Zeroes the vehicle data gauges and drops the state labels and coordinates of a vehicle whose data
+/// went stale.
+fn reset_vehicle_data(car_name: &str, account_index: &str, native_type: &str) {
+    let labels = [car_name, account_index];
+
+    for gauge in [
+        &BATTERY_LEVEL_GAUGE, &USABLE_BATTERY_LEVEL_GAUGE, &TIME_TO_FULL_CHARGE_GAUGE, &CHARGE_LIMIT_SOC_GAUGE,
+        &CHARGE_LIMIT_SOC_STD_GAUGE, &CHARGE_LEVEL_HEADROOM_GAUGE,
+        &SOFTWARE_UPDATE_DURATION_GAUGE, &CHARGER_POWER_GAUGE,
+    ] {
+        gauge.with_label_values(&labels).set(0);
+    }
+    // 0 would read as parked
+    SHIFT_VALUE_GAUGE.with_label_values(&labels).set(SHIFT_STATE_UNKNOWN_VALUE);
+
+    for gauge in [
+        &BATTERY_RANGE_GAUGE, &BATTERY_EST_RANGE_GAUGE, &BATTERY_IDEAL_RANGE_GAUGE, &CHARGE_ENERGY_ADDED_GAUGE,
+        &CHARGE_MILES_ADDED_RATED_GAUGE, &CHARGE_RATE_GAUGE, &CHARGE_RATE_KM_GAUGE,
+        &CHARGER_VOLTAGE_GAUGE, &CALCULATED_CHARGER_POWER_GAUGE, &CHARGER_ACTUAL_CURRENT_GAUGE,
+        &SPEED_GAUGE, &POWER_GAUGE, &NET_POWER_GAUGE, &ODOMETER_GAUGE, &INSIDE_TEMPERATURE_GAUGE, &OUTSIDE_TEMPERATURE_GAUGE,
+        &DRIVER_TEMPERATURE_GAUGE, &PASSENGER_TEMPERATURE_GAUGE, &GEO_HEADING_GAUGE,
+    ] {
+        gauge.with_label_values(&labels).set(0.0);
+    }
+
+    // Drop the coordinates instead of zeroing them, 0,0 is a real location off the coast of Africa
+    for gauge in [&GEO_LAT_GAUGE, &GEO_LONG_GAUGE] {
+        let _ = gauge.remove_label_values(&[car_name, native_type, account_index]);
+    }
+    for gauge in [&GEO_LAT_WGS84_GAUGE, &GEO_LONG_WGS84_GAUGE, &GEO_NATIVE_LAT_GAUGE, &GEO_NATIVE_LONG_GAUGE] {
+        let _ = gauge.remove_label_values(&labels);
+    }
+
+    // Not every API version reports HomeLink, so don't create the series if it never existed
+    let _ = HOMELINK_NEARBY_GAUGE.remove_label_values(&labels);
+    // Drop the flags instead of zeroing them, an unknown lock state shouldn't look like an unlocked vehicle
+    for gauge in [&LOCKED_GAUGE, &SENTRY_MODE_GAUGE, &USER_PRESENT_GAUGE, &VALET_MODE_GAUGE] {
+        let _ = gauge.remove_label_values(&labels);
+    }
+    for gauge in [&TPMS_PRESSURE_FL_GAUGE, &TPMS_PRESSURE_FR_GAUGE, &TPMS_PRESSURE_RL_GAUGE, &TPMS_PRESSURE_RR_GAUGE] {
+        let _ = gauge.remove_label_values(&labels);
+    }
+
+    for (_, gauge) in EXTRA_FIELD_GAUGES.iter() {
+        gauge.with_label_values(&labels).set(0.0);
+    }
+
+    for state in VehicleDriveState::SHIFT_STATE_LABELS {
+        let _ = SHIFT_INFO_GAUGE.remove_label_values(&[car_name, state, account_index]);
+    }
+    for state in VehicleChargeState::CHARGING_STATE_LABELS {
+        let _ = CHARGING_STATE_GAUGE.remove_label_values(&[car_name, state, account_index]);
+    }
+}
+
+/// Sets the series for the `current` state to 1 and removes the series of every other state, so
+/// only one state label is exported per vehicle at a time.
+fn set_state_label(gauge: &IntGaugeVec, car_name: &str, account_index: &str, states: &[&str], current: &str) {
+    for state in states.iter().filter(|state| **state != current) {
+        let _ = gauge.remove_label_values(&[car_name, state, account_index]);
+    }
+    gauge.with_label_values(&[car_name, current, account_index]).set(1);
+}
+
 #[derive(Debug, Clone)]
 pub enum CarState {
     Parked(VehicleData),
     Charging(VehicleData),
     Driving(VehicleData),
+    /// A wake up was requested and the vehicle isn't online yet.
+    Waking(Vehicle),
     Unknown,
 }
 
@@ -347,6 +1370,7 @@ impl CarState {
             CarState::Parked(_) => 1,
             CarState::Charging(_) => 2,
             CarState::Driving(_) => 3,
+            CarState::Waking(_) => 4,
         }
     }
 
@@ -357,22 +1381,33 @@ impl CarState {
         }
     }
 
-    pub fn wait(&self) -> Duration {
+    pub fn wait(&self, intervals: &PollIntervals) -> Duration {
         match self {
             CarState::Parked(_) => {
-                Duration::from_secs(30)
+                Duration::from_secs(intervals.parked_secs)
             }
             CarState::Charging(v) => {
                 if v.charge_state.fast_charger_present {
-                    Duration::from_secs(5)
+                    Duration::from_secs(intervals.fast_charging_secs)
                 } else {
-                    Duration::from_secs(15)
+                    Duration::from_secs(intervals.ac_charging_secs)
                 }
             }
             CarState::Driving(_) => {
-                Duration::from_secs(5)
+                Duration::from_secs(intervals.driving_secs)
+            }
+            CarState::Waking(_) | CarState::Unknown => Duration::from_secs(intervals.unknown_secs)
+        }
+    }
+
+    /// Same as `wait` with the configured intervals, except parked vehicles with a low battery use
+    /// `low_battery_poll_interval`.
+    pub fn wait_with(&self, config: &PollerConfig) -> Duration {
+        match self {
+            CarState::Parked(v) if v.charge_state.battery_level < config.low_battery_threshold_pct => {
+                config.low_battery_poll_interval
             }
-            CarState::Unknown => Duration::from_secs(30)
+            _ => self.wait(&config.poll_intervals),
         }
     }
 }
@@ -389,6 +1424,9 @@ impl<'a> Display for CarState {
             CarState::Driving(_) => {
                 write!(f, "Driving")
             }
+            CarState::Waking(_) => {
+                write!(f, "Waking")
+            }
             CarState::Unknown => {
                 write!(f, "Unknown")
             }
@@ -398,13 +1436,10 @@ impl<'a> Display for CarState {
 
 impl From<VehicleData> for CarState {
     fn from(v: VehicleData) -> Self {
-        let speed = v.drive_state.speed.unwrap_or_default();
-        let shift = v.drive_state.shift_state.as_deref().unwrap_or_default();
-        if shift.eq("R") || shift.eq("D") || shift.eq("N") || speed > 0.0 {
+        if v.drive_state.is_driving() {
             return CarState::Driving(v.clone());
         }
-        let charging_state = v.charge_state.charging_state.clone();
-        if charging_state.eq("Disconnected") {
+        if v.charge_state.charging_state == ChargingState::Disconnected {
             return CarState::Parked(v.clone());
         }
         CarState::Charging(v.clone())
@@ -412,141 +1447,926 @@ impl From<VehicleData> for CarState {
 }
 
 
-fn collect_vehicle_metrics(client: TeslaApiClient, vehicle_id: &i64, stop: Arc<AtomicBool>) -> Result<()> {
-    // TODO: reset error count after some duration
-    let mut car_state = CarState::Unknown;
-    let mut duration = Duration::from_secs(60);
-
-    while !stop.load(Ordering::SeqCst) {
-        match client.fetch_vehicle(&vehicle_id) {
-            Err(err) => {
-                warn!("Failed to fetch vehicle: {}", err);
-                sleep(duration);
+/// Tracks the CPU time of the current polling thread via `/proc/self/task/<tid>/stat`.
+#[cfg(target_os = "linux")]
+struct ThreadCpuTime {
+    tid: i32,
+    last_cpu_secs: f64,
+}
+
+#[cfg(target_os = "linux")]
+impl ThreadCpuTime {
+    fn current_thread() -> Self {
+        ThreadCpuTime { tid: unsafe { libc::gettid() }, last_cpu_secs: 0.0 }
+    }
+
+    fn record(&mut self, car_name: &str, account_index: &str) {
+        match self.cpu_secs() {
+            Ok(cpu_secs) => {
+                POLLING_THREAD_CPU_COUNTER
+                    .with_label_values(&[car_name, account_index])
+                    .inc_by((cpu_secs - self.last_cpu_secs).max(0.0));
+                self.last_cpu_secs = cpu_secs;
             }
-            Ok(vehicle) => {
-                let mut is_online = vehicle.is_online();
-                let display_name = &vehicle.display_name;
-                let mut error: Option<String> = None;
-
-                match (is_online, &car_state) {
-                    (false, CarState::Parked(_)) => {
-                        duration = Duration::from_secs(30);
-                    }
-                    (false, _) => {
-                        match client.wake_vehicle_poll(&vehicle_id) {
-                            Ok(_) => {
-                                is_online = true;
-                                info!("Woke up vehicle: Vehicle=\"{}\" CarState=\"{}\" is_online=\"{}\"",
-                                      display_name, car_state, is_online);
-                            }
-                            Err(err) => {
-                                duration = Duration::from_secs(60);
-                                error = Some(format!("Failed to wake up vehicle: Vehicle=\"{}\" CarState=\"{}\" is_online=\"true\" Waiting=\"{:?}\" error=\"{:?}\"",
-                                                     display_name, car_state, err, duration));
-                            }
-                        }
+            Err(err) => warn!("Failed to read polling thread CPU time: {:#}", err),
+        }
+    }
+
+    fn cpu_secs(&self) -> Result<f64> {
+        let stat = procfs::process::Process::myself()?.task_from_tid(self.tid)?.stat()?;
+        Ok((stat.utime + stat.stime) as f64 / procfs::ticks_per_second() as f64)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct ThreadCpuTime;
+
+#[cfg(not(target_os = "linux"))]
+impl ThreadCpuTime {
+    fn current_thread() -> Self {
+        ThreadCpuTime
+    }
+
+    fn record(&mut self, _car_name: &str, _account_index: &str) {}
+}
+
+fn is_circuit_open(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<TeslaApiError>() == Some(&TeslaApiError::CircuitOpen)
+}
+
+/// How long the Tesla API asked to wait before the next request, see [`TeslaApiError::RateLimited`].
+fn rate_limit_delay(err: &anyhow::Error) -> Option<Duration> {
+    match err.downcast_ref::<TeslaApiError>() {
+        Some(TeslaApiError::RateLimited(retry_after)) => Some(*retry_after),
+        _ => None,
+    }
+}
+
+/// The `error_type` of a failed request, `other` for errors that aren't a [`TeslaApiError`].
+fn api_error_type(err: &anyhow::Error) -> &'static str {
+    err.downcast_ref::<TeslaApiError>().map_or("other", TeslaApiError::error_type)
+}
+
+fn count_api_error(car_name: &str, account_index: &str, err: &anyhow::Error) {
+    API_ERRORS_COUNTER
+        .with_label_values(&[car_name, api_error_type(err), account_index])
+        .inc();
+}
+
+fn wake_vehicle(client: &dyn TeslaApiClientTrait, vehicle: &Vehicle, account_index: &str) -> Result<WakeResult> {
+    let labels = [vehicle.display_name.as_str(), account_index];
+    let attempts = WAKE_ATTEMPTS_COUNTER.with_label_values(&labels);
+    let api_calls = API_CALLS_COUNTER.with_label_values(&[&vehicle.display_name, "wake_up", account_index]);
+
+    let wake_result = client.wake_vehicle_poll_observed(&vehicle.id, &mut || {
+        attempts.inc();
+        api_calls.inc();
+    })?;
+
+    WAKE_SUCCESS_COUNTER
+        .with_label_values(&labels)
+        .inc();
+    WAKE_DURATION_HISTOGRAM
+        .with_label_values(&labels)
+        .observe(wake_result.total_duration.as_secs_f64());
+    Ok(wake_result)
+}
+
+fn fetch_vehicle_data(client: &dyn TeslaApiClientTrait, vehicle: &Vehicle, account_index: &str, config: &PollerConfig) -> Result<VehicleData> {
+    let api_calls = API_CALLS_COUNTER.with_label_values(&[&vehicle.display_name, "vehicle_data", account_index]);
+    api_calls.inc();
+    match client.fetch_vehicle_data(&vehicle.id) {
+        Err(err) if config.retry_on_vehicle_unavailable
+            && matches!(err.downcast_ref::<TeslaApiError>(), Some(TeslaApiError::VehicleUnavailable())) => {
+            info!("Vehicle unavailable, waking before retrying: Vehicle=\"{}\"", vehicle.display_name);
+            wake_vehicle(client, vehicle, account_index)
+                .with_context(|| format!("Failed to wake unavailable vehicle {}", vehicle.id))?;
+            api_calls.inc();
+            client.fetch_vehicle_data(&vehicle.id)
+                .with_context(|| format!("Failed to fetch vehicle data for vehicle {} after waking", vehicle.id))
+        }
+        result => result,
+    }
+}
+
+/// Fetches and records the data of an awake vehicle once, without a polling thread, for callers
+/// that schedule polls themselves such as a cron job.
+///
+/// Blocking like [`TeslaApiClientTrait`], an async caller can run it with `spawn_blocking`. The
+/// metrics are the exporter's, which can be gathered from the registry returned by [`register`],
+/// labelled with `account_index` 1.
+pub fn fetch_and_record(client: &dyn TeslaApiClientTrait, vehicle_id: i64) -> Result<CarState> {
+    let vehicle_data = client.fetch_vehicle_data(&vehicle_id)?;
+    let car_state = record_and_publish(&vehicle_data, "1");
+    let labels = [vehicle_data.display_name.as_str(), "1"];
+
+    CAR_STATE_GAUGE
+        .with_label_values(&labels)
+        .set(car_state.value());
+
+    CAR_ONLINE_GAUGE
+        .with_label_values(&labels)
+        .set(1);
+
+    Ok(car_state)
+}
+
+/// What the polling loop remembers about a vehicle between polls.
+#[derive(Debug)]
+struct PreviousState {
+    car_state: CarState,
+    /// How long to wait before the next poll.
+    duration: Duration,
+    /// When `record()` last ran, used to honour `metrics_sample_interval`.
+    last_recorded: Option<Instant>,
+    /// When vehicle data was last fetched successfully.
+    last_data_at: Option<Instant>,
+    /// Whether the gauges were already zeroed because the data went stale.
+    is_stale: bool,
+    /// The `native_type` label the geo gauges were last recorded with.
+    native_type: String,
+    charge_energy: ChargeEnergyTracker,
+    sentry_mode: SentryModeTracker,
+    stale_detector: StaleDataDetector,
+    /// The vehicle's name as of the last fetch, labels the attempts of polls that fail to fetch it.
+    display_name: Option<String>,
+    /// When a poll last completed without errors.
+    last_success: Option<Instant>,
+    /// Which of the configured accounts the vehicle belongs to, counting from 1.
+    account_index: usize,
+}
+
+impl Default for PreviousState {
+    fn default() -> Self {
+        PreviousState {
+            car_state: CarState::Unknown,
+            duration: Duration::from_secs(60),
+            last_recorded: None,
+            last_data_at: None,
+            is_stale: false,
+            native_type: String::new(),
+            charge_energy: ChargeEnergyTracker::default(),
+            sentry_mode: SentryModeTracker::default(),
+            stale_detector: StaleDataDetector::default(),
+            display_name: None,
+            last_success: None,
+            account_index: 1,
+        }
+    }
+}
+
+impl PreviousState {
+    fn is_sample_due(&self, config: &PollerConfig) -> bool {
+        self.last_recorded
+            .is_none_or(|at| at.elapsed() >= config.metrics_sample_interval)
+    }
+
+    fn has_gone_stale(&self, config: &PollerConfig) -> bool {
+        config.metric_staleness > Duration::from_secs(0)
+            && !self.is_stale
+            && self.last_data_at.is_some_and(|at| at.elapsed() >= config.metric_staleness)
+    }
+}
+
+/// A drop of `charge_energy_added` larger than this (kWh) means a new charging session started,
+/// smaller drops are treated as noise.
+const CHARGE_SESSION_RESET_KWH: f64 = 5.0;
+
+/// Turns the per-session `charge_energy_added` into increments of a counter that never decreases.
+#[derive(Debug, Default)]
+struct ChargeEnergyTracker {
+    last_energy_added: Option<f64>,
+}
+
+impl ChargeEnergyTracker {
+    /// Returns the energy added since the previous observation (kWh).
+    fn observe(&mut self, energy_added: f64) -> f64 {
+        let increment = match self.last_energy_added {
+            None => 0.0,
+            Some(last) if energy_added >= last => energy_added - last,
+            Some(last) if last - energy_added > CHARGE_SESSION_RESET_KWH => energy_added,
+            // Noise, keep the higher value so the energy isn't counted twice once it recovers
+            Some(_) => return 0.0,
+        };
+        self.last_energy_added = Some(energy_added);
+        increment
+    }
+}
+
+/// Turns the `sentry_mode` flag of successive polls into how long sentry mode was active.
+#[derive(Debug, Default)]
+struct SentryModeTracker {
+    /// When sentry mode was last seen active, `None` while it is off.
+    active_since: Option<Instant>,
+}
+
+impl SentryModeTracker {
+    /// Returns how long sentry mode was active since the previous observation.
+    fn observe(&mut self, active: bool) -> Duration {
+        self.observe_at(active, Instant::now())
+    }
+
+    fn observe_at(&mut self, active: bool, now: Instant) -> Duration {
+        let elapsed = match (active, self.active_since) {
+            (true, Some(since)) => now.saturating_duration_since(since),
+            _ => Duration::from_secs(0),
+        };
+        self.active_since = if active { Some(now) } else { None };
+        elapsed
+    }
+}
+
+/// Polls in a row returning the same `drive_state.timestamp` before a response counts as stale.
+const STALE_RESPONSE_POLLS: u32 = 2;
+/// Stale polls in a row after which the poll interval is doubled until the data advances again.
+const STALE_RESPONSE_BACKOFF_POLLS: u32 = 5;
+
+/// Detects cached `vehicle_data` responses, which repeat the previous `drive_state.timestamp`.
+#[derive(Debug, Default)]
+struct StaleDataDetector {
+    last_timestamp: Option<i64>,
+    repeats: u32,
+}
+
+impl StaleDataDetector {
+    fn observe(&mut self, timestamp: i64) {
+        if self.last_timestamp == Some(timestamp) {
+            self.repeats += 1;
+        } else {
+            self.last_timestamp = Some(timestamp);
+            self.repeats = 0;
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.repeats > STALE_RESPONSE_POLLS
+    }
+
+    fn should_back_off(&self) -> bool {
+        self.repeats > STALE_RESPONSE_POLLS + STALE_RESPONSE_BACKOFF_POLLS
+    }
+}
+
+/// Polls the vehicle once: wakes it or fetches and records its data, then updates `previous`,
+/// including the `duration` to wait before the next poll. Returns the vehicle's display name
+/// unless the vehicle couldn't be fetched.
+fn poll_once(client: &dyn TeslaApiClientTrait, vehicle_id: &i64, config: &PollerConfig, previous: &mut PreviousState) -> Option<String> {
+    let account_index = previous.account_index.to_string();
+    let vehicle = match client.fetch_vehicle(vehicle_id) {
+        Err(err) => {
+            // Labeled with the vehicle id until the first poll got the display name
+            let car_name = previous.display_name.clone().unwrap_or_else(|| vehicle_id.to_string());
+            POLL_ATTEMPTS_COUNTER
+                .with_label_values(&[&car_name, &account_index])
+                .inc();
+            count_api_error(&car_name, &account_index, &err);
+            if is_circuit_open(&err) {
+                previous.duration = Duration::from_secs(60);
+                warn!("Skipping poll while the Tesla API is down: VehicleId=\"{}\" Waiting=\"{:?}\"",
+                      vehicle_id, previous.duration);
+            } else if let Some(retry_after) = rate_limit_delay(&err) {
+                previous.duration = retry_after;
+                warn!("Rate limited by the Tesla API: VehicleId=\"{}\" Waiting=\"{:?}\"", vehicle_id, previous.duration);
+            } else {
+                warn!("Failed to fetch vehicle: {:#}", err);
+            }
+            return None;
+        }
+        Ok(vehicle) => vehicle,
+    };
+    previous.display_name = Some(vehicle.display_name.clone());
+    let display_name = &vehicle.display_name;
+    let labels = [display_name.as_str(), &account_index];
+    POLL_ATTEMPTS_COUNTER
+        .with_label_values(&labels)
+        .inc();
+    API_CALLS_COUNTER
+        .with_label_values(&[display_name, "vehicle", &account_index])
+        .inc();
+
+    let mut is_online = vehicle.is_online();
+    let mut error: Option<String> = None;
+    let mut fetched_data = false;
+    let previous_car_state = previous.car_state.clone();
+
+    match (is_online, &previous.car_state) {
+        (false, CarState::Parked(_)) => {
+            previous.duration = previous.car_state.wait_with(config);
+        }
+        (false, _) => {
+            previous.car_state = CarState::Waking(vehicle.clone());
+            CAR_STATE_GAUGE
+                .with_label_values(&labels)
+                .set(previous.car_state.value());
+            CAR_ONLINE_GAUGE
+                .with_label_values(&labels)
+                .set(0);
+
+            match wake_vehicle(client, &vehicle, &account_index) {
+                Ok(wake_result) => {
+                    is_online = true;
+                    // Online but without data yet, the next poll fetches it
+                    previous.car_state = CarState::Unknown;
+                    previous.duration = previous.car_state.wait(&config.poll_intervals);
+                    info!("Woke up vehicle: Vehicle=\"{}\" CarState=\"{}\" is_online=\"{}\" attempts=\"{}\" duration=\"{:?}\"",
+                          display_name, previous.car_state, is_online, wake_result.attempts, wake_result.total_duration);
+                }
+                Err(err) => {
+                    count_api_error(display_name, &account_index, &err);
+                    previous.duration = rate_limit_delay(&err).unwrap_or(Duration::from_secs(60));
+                    error = Some(format!("Failed to wake up vehicle: Vehicle=\"{}\" CarState=\"{}\" is_online=\"{}\" Waiting=\"{:?}\" error=\"{:#}\"",
+                                         display_name, previous.car_state, is_online, previous.duration, err));
+                    previous.car_state = CarState::Unknown;
+                }
+            }
+        }
+        (true, _) => {
+            match fetch_vehicle_data(client, &vehicle, &account_index, config) {
+                Ok(vehicle_data) => {
+                    fetched_data = true;
+                    previous.last_data_at = Some(Instant::now());
+                    previous.is_stale = false;
+                    previous.stale_detector.observe(vehicle_data.drive_state.timestamp);
+                    CHARGE_SESSIONS_ENERGY_COUNTER
+                        .with_label_values(&labels)
+                        .inc_by(previous.charge_energy.observe(vehicle_data.charge_state.charge_energy_added));
+                    if vehicle_data.vehicle_state.api_version_supported_features().supports_sentry_mode {
+                        SENTRY_MODE_ACTIVE_COUNTER
+                            .with_label_values(&labels)
+                            .inc_by(previous.sentry_mode.observe(vehicle_data.vehicle_state.sentry_mode).as_secs_f64());
                     }
-                    (true, _) => {
-                        match client.fetch_vehicle_data(&vehicle_id) {
-                            Ok(vehicle_data) => {
-                                car_state = record(&vehicle_data);
-                                duration = car_state.wait();
-                            }
-                            Err(err) => {
-                                car_state = CarState::Unknown;
-                                duration = Duration::from_secs(60);
-                                error = Some(format!("Failed to fetch vehicle data: Vehicle=\"{}\" CarState=\"{}\" is_online=\"{}\" Waiting=\"{:?}\" error=\"{:?}\"",
-                                                     display_name, car_state, is_online, duration, err));
-                            }
+                    previous.car_state = if previous.is_sample_due(config) {
+                        previous.last_recorded = Some(Instant::now());
+                        previous.native_type = vehicle_data.drive_state.native_type_label().to_string();
+                        record_and_publish(&vehicle_data, &account_index)
+                    } else {
+                        CarState::from(vehicle_data)
+                    };
+                    previous.duration = previous.car_state.wait_with(config);
+
+                    if previous.stale_detector.is_stale() {
+                        STALE_RESPONSE_COUNTER
+                            .with_label_values(&labels)
+                            .inc();
+                        if previous.stale_detector.should_back_off() {
+                            previous.duration *= 2;
                         }
+                        warn!("Vehicle data is not advancing: Vehicle=\"{}\" repeats=\"{}\" Waiting=\"{:?}\"",
+                              display_name, previous.stale_detector.repeats, previous.duration);
                     }
                 }
+                Err(err) => {
+                    count_api_error(display_name, &account_index, &err);
+                    previous.car_state = CarState::Unknown;
+                    previous.duration = rate_limit_delay(&err).unwrap_or(Duration::from_secs(60));
+                    error = Some(format!("Failed to fetch vehicle data: Vehicle=\"{}\" CarState=\"{}\" is_online=\"{}\" Waiting=\"{:?}\" error=\"{:#}\"",
+                                         display_name, previous.car_state, is_online, previous.duration, err));
+                }
+            }
+        }
+    }
+
+    CAR_STATE_GAUGE
+        .with_label_values(&labels)
+        .set(previous.car_state.value());
 
-                CAR_STATE_GAUGE
-                    .with_label_values(&[&display_name])
-                    .set(car_state.value());
+    if previous.car_state.value() != previous_car_state.value() {
+        publish_car_state(display_name, &previous_car_state, &previous.car_state);
+    }
 
-                CAR_ONLINE_GAUGE
-                    .with_label_values(&[&display_name])
-                    .set(if is_online { 1 } else { 0 });
+    CAR_ONLINE_GAUGE
+        .with_label_values(&labels)
+        .set(if is_online { 1 } else { 0 });
 
-                match error {
-                    None => {
-                        info!("Collected vehicle metrics: Vehicle=\"{}\" CarState=\"{}\" is_online=\"{}\" Waiting=\"{:?}\"",
-                              display_name, car_state, is_online, duration);
-                    }
-                    Some(message) => {
-                        warn!("{}", message);
-                    }
-                }
+    CALENDAR_ENABLED_GAUGE
+        .with_label_values(&labels)
+        .set(if vehicle.calendar_enabled { 1 } else { 0 });
+
+    VEHICLE_INFO_GAUGE
+        .with_label_values(&[display_name, &vehicle.vin, vehicle.color.as_deref().unwrap_or_default(),
+                             &account_index])
+        .set(1);
+
+    if !is_online && previous.has_gone_stale(config) {
+        info!("Vehicle data went stale, resetting metrics: Vehicle=\"{}\"", display_name);
+        reset_vehicle_data(display_name, &account_index, &previous.native_type);
+        previous.is_stale = true;
+    }
+
+    match error {
+        None => {
+            previous.last_success = Some(Instant::now());
+            if fetched_data {
+                POLL_SUCCESSES_COUNTER
+                    .with_label_values(&labels)
+                    .inc();
+            }
+            info!("Collected vehicle metrics: Vehicle=\"{}\" CarState=\"{}\" is_online=\"{}\" Waiting=\"{:?}\"",
+                  display_name, previous.car_state, is_online, previous.duration);
+        }
+        Some(message) => {
+            warn!("{}", message);
+        }
+    }
+
+    Some(vehicle.display_name)
+}
+
+fn collect_vehicle_metrics(client: impl TeslaApiClientTrait + Clone + Send + 'static, vehicle_id: &i64, account_index: usize,
+                           config: &PollerConfig, stop: StopSignal, health: &PollerHealth, states: Option<&mpsc::Sender<CarState>>) -> Result<()> {
+    // TODO: reset error count after some duration
+    let mut previous = PreviousState { account_index, ..PreviousState::default() };
+    let mut thread_cpu_time = ThreadCpuTime::current_thread();
+
+    #[cfg(feature = "sqlite")]
+    restore_vehicle_data(*vehicle_id, account_index);
+
+    while !stop.is_stopped() {
+        #[cfg(feature = "sqlite")]
+        let last_data_at = previous.last_data_at;
+        let display_name = poll_once(&client, vehicle_id, config, &mut previous);
+        #[cfg(feature = "sqlite")]
+        if display_name.is_some() {
+            store_vehicle_data(*vehicle_id, &previous, last_data_at);
+        }
+        if let Some(last_success) = previous.last_success {
+            health.record_success(*vehicle_id, last_success);
+        }
+        if let Some(display_name) = display_name {
+            thread_cpu_time.record(&display_name, &previous.account_index.to_string());
 
-                sleep(duration);
+            if let Some(states) = states {
+                if states.send(previous.car_state.clone()).is_err() {
+                    info!("Stopped watching vehicle state: Vehicle=\"{}\"", display_name);
+                    return Ok(());
+                }
             }
         }
+
+        if !stop.sleep(previous.duration) {
+            break;
+        }
     }
     Ok(())
 }
 
+/// Records the vehicle data stored before the last restart, so the gauges don't start at zero.
+#[cfg(feature = "sqlite")]
+fn restore_vehicle_data(vehicle_id: i64, account_index: usize) {
+    let state_store = match &*STATE_STORE {
+        Some(state_store) => state_store,
+        None => return,
+    };
+    match state_store.load(vehicle_id) {
+        Ok(Some(stored)) => {
+            info!("Restored vehicle data: Vehicle=\"{}\" UpdatedAt=\"{}\"", stored.vehicle_data.display_name, stored.updated_at);
+            record(&stored.vehicle_data, &account_index.to_string());
+        }
+        Ok(None) => {}
+        Err(err) => warn!("Failed to restore vehicle data: {:#}", err),
+    }
+}
+
+/// Stores the vehicle data if the last poll fetched it, otherwise only notes that the vehicle was seen.
+#[cfg(feature = "sqlite")]
+fn store_vehicle_data(vehicle_id: i64, previous: &PreviousState, last_data_at: Option<Instant>) {
+    let state_store = match &*STATE_STORE {
+        Some(state_store) => state_store,
+        None => return,
+    };
+    let result = match &previous.car_state {
+        CarState::Parked(vehicle_data) | CarState::Charging(vehicle_data) | CarState::Driving(vehicle_data)
+        if previous.last_data_at != last_data_at => state_store.save(vehicle_id, vehicle_data),
+        _ => state_store.mark_seen(vehicle_id),
+    };
+    if let Err(err) = result {
+        warn!("Failed to store vehicle data: {:#}", err);
+    }
+}
+
+/// Polls a vehicle on a background thread the same way the exporter does, and sends every
+/// observed `CarState` down the returned channel. Prometheus gauges are still updated.
+///
+/// The thread is added to `jobs`, so it stops with the other jobs on shutdown. It also exits
+/// after the receiver is dropped.
+pub fn watch_vehicle_state(client: Arc<TeslaApiClient>, vehicle_id: i64, config: PollerConfig, jobs: &mut JobHandles) -> mpsc::Receiver<CarState> {
+    let (sender, receiver) = mpsc::channel();
+    let stop = jobs.get_stop();
+    let health = jobs.health().clone();
+    jobs.add_handle(thread::spawn(move || {
+        if let Err(err) = collect_vehicle_metrics(CircuitBreaker::new((*client).clone()), &vehicle_id, 1, &config, stop, &health, Some(&sender)) {
+            warn!("Failed to watch vehicle state: {:?}", err);
+        }
+        health.remove(&vehicle_id);
+    }));
+    receiver
+}
+
+/// How often `tesla_api_calls_per_vehicle_per_hour` is updated.
+const API_CALL_RATE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const API_CALL_RATE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// The `car_name` and `account_index` labels of a vehicle's series.
+type VehicleLabels = (String, String);
+
+/// The API call rate of every vehicle over the last hour, from snapshots of the call counter.
+#[derive(Debug, Default)]
+struct ApiCallRate {
+    totals: VecDeque<(Instant, HashMap<VehicleLabels, f64>)>,
+}
+
+impl ApiCallRate {
+    /// Adds the current call totals by vehicle, returns the calls per hour since the oldest
+    /// snapshot of the last hour. Nothing is returned for the first snapshot.
+    fn observe_at(&mut self, now: Instant, totals: HashMap<VehicleLabels, f64>) -> HashMap<VehicleLabels, f64> {
+        while let Some((at, _)) = self.totals.front() {
+            if now.saturating_duration_since(*at) <= API_CALL_RATE_WINDOW {
+                break;
+            }
+            self.totals.pop_front();
+        }
+
+        let rates = match self.totals.front() {
+            Some((since, oldest)) if now > *since => {
+                let hours = (now - *since).as_secs_f64() / 3600.0;
+                totals
+                    .iter()
+                    .map(|(vehicle, total)| {
+                        let calls = total - oldest.get(vehicle).copied().unwrap_or(0.0);
+                        (vehicle.clone(), calls / hours)
+                    })
+                    .collect()
+            }
+            _ => HashMap::new(),
+        };
+        self.totals.push_back((now, totals));
+        rates
+    }
+}
+
+/// `tesla_api_calls_total` summed over the endpoints, by vehicle.
+fn api_call_totals() -> HashMap<VehicleLabels, f64> {
+    let mut totals = HashMap::new();
+    for family in API_CALLS_COUNTER.collect() {
+        for metric in family.get_metric() {
+            let label = |name: &str| metric.get_label().iter()
+                .find(|label| label.get_name() == name)
+                .map(|label| label.get_value().to_string());
+            if let (Some(car_name), Some(account_index)) = (label("car_name"), label("account_index")) {
+                *totals.entry((car_name, account_index)).or_insert(0.0) += metric.get_counter().get_value();
+            }
+        }
+    }
+    totals
+}
+
+/// Updates `tesla_api_calls_per_vehicle_per_hour` every 5 minutes, so the API call rate can be
+/// graphed without Prometheus recording rules.
+pub struct StatsAggregator {
+    api_call_rate: ApiCallRate,
+}
+
+impl StatsAggregator {
+    pub fn new() -> Self {
+        StatsAggregator { api_call_rate: ApiCallRate::default() }
+    }
+
+    /// Aggregates until `stop` is set.
+    pub fn spawn(mut self, stop: StopSignal) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            for ((car_name, account_index), rate) in self.api_call_rate.observe_at(Instant::now(), api_call_totals()) {
+                API_CALL_RATE_GAUGE
+                    .with_label_values(&[&car_name, &account_index])
+                    .set(rate);
+            }
+            if !stop.sleep(API_CALL_RATE_INTERVAL) {
+                return;
+            }
+        })
+    }
+}
+
+impl Default for StatsAggregator {
+    fn default() -> Self {
+        StatsAggregator::new()
+    }
+}
+
+const CHARGE_HISTORY_SESSIONS: usize = 30;
+
+/// Seeds the historical energy counter from the most recent charging sessions.
+fn backfill_charge_history(client: &TeslaApiClient, vehicle: &Vehicle, account_index: usize) {
+    match client.fetch_charge_history(&vehicle.id) {
+        Ok(mut sessions) => {
+            sessions.sort_by_key(|session| std::cmp::Reverse(session.start_time));
+            let energy_added: f64 = sessions
+                .iter()
+                .take(CHARGE_HISTORY_SESSIONS)
+                .map(|session| session.energy_added)
+                .sum();
+
+            HISTORICAL_CHARGE_ENERGY_COUNTER
+                .with_label_values(&[&vehicle.display_name, &account_index.to_string()])
+                .inc_by(energy_added);
+
+            info!("Backfilled charge history: Vehicle=\"{}\" sessions=\"{}\" energy_added=\"{}\"",
+                  vehicle.display_name, sessions.len().min(CHARGE_HISTORY_SESSIONS), energy_added);
+        }
+        Err(err) => {
+            warn!("Failed to backfill charge history: Vehicle=\"{}\" error=\"{:#}\"", vehicle.display_name, err);
+        }
+    }
+}
+
+/// Records the status of an energy site every `POLL_ENERGY_SITE_SECS` until `stop` is set.
+fn collect_energy_site_metrics(client: TeslaApiClient, site_id: i64, site_name: String, stop: StopSignal) {
+    loop {
+        match client.fetch_energy_site_status(&site_id) {
+            Ok(status) => record_energy_site(&site_name, &status),
+            Err(err) => warn!("Failed to fetch energy site status: Site=\"{}\" error=\"{:#}\"", site_name, err),
+        }
+
+        if !stop.sleep(Duration::from_secs(POLL_ENERGY_SITE_SECS)) {
+            return;
+        }
+    }
+}
+
+fn record_energy_site(site_name: &str, status: &EnergySiteStatus) {
+    ENERGY_BATTERY_POWER_GAUGE
+        .with_label_values(&[site_name])
+        .set(status.battery_power);
+
+    ENERGY_SOLAR_POWER_GAUGE
+        .with_label_values(&[site_name])
+        .set(status.solar_power);
+
+    ENERGY_GRID_POWER_GAUGE
+        .with_label_values(&[site_name])
+        .set(status.grid_power);
+
+    ENERGY_PERCENTAGE_CHARGED_GAUGE
+        .with_label_values(&[site_name])
+        .set(status.percentage_charged);
+}
 
 fn start_jobs() -> Result<JobHandles> {
     info!("Starting poller");
 
-    match TeslaApiClient::create(AuthToken::from_env()) {
+    let accounts = match AuthToken::accounts_from_env() {
+        Ok(accounts) => accounts,
+        Err(err) => {
+            error!("Invalid Tesla accounts: {:#}", err);
+            return Err(err);
+        }
+    };
+    let account_count = accounts.len();
+
+    let mut clients = Vec::new();
+    for (index, auth_token) in accounts.into_iter().enumerate() {
+        let account_index = index + 1;
+        let mut builder = TeslaApiClient::builder(auth_token).retry_policy(RetryPolicy::default());
+        if account_count > 1 {
+            if let Some(token_store) = FileTokenStore::from_env() {
+                builder = builder.token_store(Arc::new(token_store.for_account(account_index)));
+            }
+        }
+        match builder.build() {
+            Ok(client) => clients.push((account_index, client)),
+            Err(err) if account_count == 1 => {
+                error!("Failed to authenticate with tesla API {}", err);
+                return Err(err);
+            }
+            Err(err) => error!("Failed to authenticate with tesla API: Account=\"{}\" error=\"{}\"", account_index, err),
+        }
+    }
+    if clients.is_empty() {
+        return Err(anyhow!("Failed to authenticate any of the {} Tesla accounts", account_count));
+    }
+
+    let config = match PollerConfig::from_env() {
+        Ok(config) => config,
         Err(err) => {
-            error!("Failed to authenticate with tesla API {}", err);
-            Err(err)
-        }
-        Ok(client) => {
-            let mut handles = JobHandles::default();
-            let vehicles = client.fetch_vehicles()?;
-            for v in vehicles {
-                info!("Started collecting vehicle metrics: Vehicle=\"{}\"", &v.display_name);
+            error!("Invalid poller configuration: {:#}", err);
+            return Err(err);
+        }
+    };
+    delay_first_api_call(config.startup_delay);
+
+    let vehicle_filter = VehicleFilter::from_env();
+    let mut handles = JobHandles::new(PollerHealth::new(config.healthz_max_age));
+    handles.add_handle(StatsAggregator::new().spawn(handles.get_stop()));
+    for (account_index, client) in clients {
+        if let Some(token_store) = client.token_store().filter(|_| config.token_reload_interval > Duration::from_secs(0)) {
+            let watcher = TokenWatcher::new(client.auth_token(), token_store, config.token_reload_interval);
+            handles.add_handle(watcher.spawn(handles.get_stop()));
+        }
+
+        match start_account_jobs(&mut handles, &config, &vehicle_filter, account_index, client) {
+            Ok(_) => {}
+            Err(err) if account_count == 1 => return Err(err),
+            Err(err) => error!("Failed to poll account: Account=\"{}\" error=\"{:#}\"", account_index, err),
+        }
+    }
+    Ok(handles)
+}
+
+/// Starts the token refresh and the collection of the account's energy sites and vehicles.
+fn start_account_jobs(handles: &mut JobHandles, config: &PollerConfig, vehicle_filter: &VehicleFilter, account_index: usize,
+                      client: TeslaApiClient) -> Result<()> {
+    handles.add_handle(TokenRefreshScheduler::new(client.clone()).spawn(handles.get_stop()));
+
+    match client.fetch_products() {
+        Ok(products) => {
+            for (site_id, site_name) in products.iter().filter_map(Product::energy_site) {
+                info!("Started collecting energy site metrics: Site=\"{}\"", site_name);
                 let s = handles.get_stop();
                 let c = client.clone();
-                handles.add_handle(thread::spawn(move || {
-                    if let Err(err) = collect_vehicle_metrics(c, &v.id, s) {
-                        warn!("Failed to collect vehicle metrics: {:?}", err);
-                    }
-                }));
+                let site_name = site_name.to_string();
+                handles.add_handle(thread::spawn(move || collect_energy_site_metrics(c, site_id, site_name, s)));
             }
-            Ok(handles)
         }
+        Err(err) => warn!("Failed to fetch energy sites: {:#}", err),
+    }
+
+    let vehicles = client.fetch_vehicles().context("Failed to fetch vehicles to poll")?;
+    let breaker = CircuitBreaker::new(client.clone());
+    for v in vehicles {
+        if !vehicle_filter.allows(&v) {
+            info!("Skipped filtered vehicle: Vehicle=\"{}\" Account=\"{}\"", &v.display_name, account_index);
+            continue;
+        }
+        backfill_charge_history(&client, &v, account_index);
+        info!("Started collecting vehicle metrics: Vehicle=\"{}\" Account=\"{}\"", &v.display_name, account_index);
+        let s = handles.get_stop();
+        let c = breaker.clone();
+        let cfg = config.clone();
+        let health = handles.health().clone();
+        handles.add_handle(thread::spawn(move || {
+            if let Err(err) = collect_vehicle_metrics(c, &v.id, account_index, &cfg, s, &health, None) {
+                warn!("Failed to collect vehicle metrics: {:?}", err);
+            }
+            health.remove(&v.id);
+        }));
+    }
+    Ok(())
+}
+
+/// When each vehicle collection thread last polled its vehicle successfully, served by `GET /healthz`.
+///
+/// A vehicle is removed once its thread exits, so only live threads count towards the health.
+#[derive(Debug, Clone)]
+pub struct PollerHealth {
+    max_age: Duration,
+    last_success: Arc<Mutex<HashMap<i64, Instant>>>,
+}
+
+/// The body of `GET /healthz`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub healthy: bool,
+    /// Seconds since the last successful poll, by vehicle id.
+    pub last_success_secs: BTreeMap<i64, u64>,
+}
+
+impl PollerHealth {
+    pub fn new(max_age: Duration) -> Self {
+        PollerHealth { max_age, last_success: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    pub fn record_success(&self, vehicle_id: i64, at: Instant) {
+        self.last_success.lock().expect("Poller health lock is poisoned").insert(vehicle_id, at);
+    }
+
+    pub fn remove(&self, vehicle_id: &i64) {
+        self.last_success.lock().expect("Poller health lock is poisoned").remove(vehicle_id);
+    }
+
+    /// How many live vehicle collection threads have polled their vehicle successfully at least once.
+    pub fn polled_vehicle_count(&self) -> usize {
+        self.last_success.lock().expect("Poller health lock is poisoned").len()
+    }
+
+    /// Healthy while at least one vehicle was polled successfully within `max_age`.
+    pub fn report(&self) -> HealthReport {
+        let last_success = self.last_success.lock().expect("Poller health lock is poisoned");
+        let last_success_secs: BTreeMap<i64, u64> = last_success
+            .iter()
+            .map(|(vehicle_id, at)| (*vehicle_id, at.elapsed().as_secs()))
+            .collect();
+        HealthReport {
+            healthy: last_success.values().any(|at| at.elapsed() <= self.max_age),
+            last_success_secs,
+        }
+    }
+}
+
+impl Default for PollerHealth {
+    fn default() -> Self {
+        PollerHealth::new(PollerConfig::default().healthz_max_age)
     }
 }
 
 pub struct JobHandles {
-    stop: Arc<AtomicBool>,
-    handles: Vec<JoinHandle<()>>,
+    stop: StopSignal,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+    health: PollerHealth,
 }
 
 impl JobHandles {
+    pub fn new(health: PollerHealth) -> Self {
+        JobHandles {
+            stop: StopSignal::new(),
+            handles: Mutex::new(Vec::new()),
+            health,
+        }
+    }
+
     pub fn add_handle(&mut self, handle: JoinHandle<()>) {
-        self.handles.push(handle);
+        self.handles.get_mut().expect("Job handles lock is poisoned").push(handle);
     }
 
-    pub fn get_stop(&self) -> Arc<AtomicBool> {
+    pub fn get_stop(&self) -> StopSignal {
         self.stop.clone()
     }
+
+    pub fn health(&self) -> &PollerHealth {
+        &self.health
+    }
+
+    /// Blocks until every job has finished, i.e. for as long as the poller runs.
+    pub fn wait(&self) {
+        // Joined outside of the lock, so a concurrent shutdown doesn't wait on it or find it poisoned
+        let handles: Vec<JoinHandle<()>> = self.handles.lock().expect("Job handles lock is poisoned").drain(..).collect();
+        for handle in handles {
+            if handle.join().is_err() {
+                error!("Job panicked");
+            }
+        }
+    }
+
+    /// Signals every job to stop and waits until they have finished their current request.
+    pub fn shutdown(&self) {
+        self.stop.stop();
+        self.wait();
+    }
 }
 
 impl Default for JobHandles {
     fn default() -> Self {
-        JobHandles {
-            stop: Arc::new(AtomicBool::new(false)),
-            handles: Vec::new(),
-        }
+        JobHandles::new(PollerHealth::default())
     }
 }
 
 impl Drop for JobHandles {
     fn drop(&mut self) {
-        self.stop.store(true, Ordering::SeqCst);
-        for handle in self.handles.drain(0..) {
-            handle.join().unwrap();
-        }
+        self.shutdown();
+    }
+}
+
+/// Shuts the jobs down and exits on SIGINT or SIGTERM. Rocket never returns from `launch()`, so
+/// without this the threads would be killed mid-request.
+fn shutdown_on_signal(jobs: Arc<JobHandles>) {
+    let result = ctrlc::set_handler(move || {
+        info!("Shutting down poller");
+        jobs.shutdown();
+        info!("Stopped poller");
+        process::exit(0);
+    });
+    if let Err(err) = result {
+        warn!("Failed to install shutdown handler: {}", err);
     }
 }
 
+/// Polls without serving HTTP, writing the metrics to stdout after each poll instead.
+///
+/// Blocks until the poller stops, returns an error if it could not start.
+static STARTUP_DELAY: Once = Once::new();
+
+/// Waits `STARTUP_DELAY_SECS` once per process, whichever of the startup diagnostics and the
+/// poller calls the Tesla API first.
+pub fn delay_first_api_call(delay: Duration) {
+    STARTUP_DELAY.call_once(|| {
+        if delay > Duration::from_secs(0) {
+            info!("Delaying first API call: Waiting=\"{:?}\"", delay);
+            sleep(delay);
+        }
+    });
+}
+
+pub fn run_standalone() -> Result<()> {
+    let prometheus = register();
+    OUTPUTS
+        .write()
+        .expect("Outputs lock is poisoned")
+        .push(Box::new(StdoutOutput::new(prometheus.registry().clone())));
+
+    let jobs = Arc::new(start_jobs()?);
+    shutdown_on_signal(jobs.clone());
+    jobs.wait();
+    Ok(())
+}
+
 pub struct Poller;
 
 impl Poller {
@@ -565,10 +2385,479 @@ impl Fairing for Poller {
 
     fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
         let prometheus = register();
+        let jobs = Arc::new(start_jobs().unwrap_or_default());
+        shutdown_on_signal(jobs.clone());
 
         Ok(rocket
+            .attach(BasicAuthGuard::from_env())
             .attach(prometheus.clone())
             .mount("/metrics", prometheus)
-            .manage(start_jobs().unwrap_or_default()))
+            .mount("/metrics", routes![storage::summary])
+            .mount("/history", routes![storage::history])
+            .mount("/", routes![routes::health, routes::ready, routes::healthz])
+            .manage(jobs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::webhook::WebhookOutput;
+    use crate::testing::FakeTeslaApiClient;
+    use crate::tesla_api_client::dtos::ShiftState;
+
+    use super::*;
+
+    /// Scripts a poll finding the vehicle in the state of `vehicle_data`.
+    fn push(client: &FakeTeslaApiClient, vehicle_data: VehicleData) {
+        let mut polled = vehicle(&vehicle_data.state);
+        polled.display_name = vehicle_data.display_name.clone();
+        client.push_vehicle(polled);
+        client.push_vehicle_data(vehicle_data);
+    }
+
+    const MOCK_CAR_NAME: &str = "Mock Auto";
+
+    fn vehicle(state: &str) -> Vehicle {
+        Vehicle {
+            id: 1,
+            display_name: MOCK_CAR_NAME.to_string(),
+            state: state.to_string(),
+            calendar_enabled: false,
+            color: None,
+            vin: String::new(),
+            extra: HashMap::new(),
+        }
+    }
+
+    fn vehicle_data() -> VehicleData {
+        let mut vehicle_data: VehicleData = serde_json::from_str(include_str!("../tests/fixtures/vehicle_data.json"))
+            .expect("Invalid vehicle data fixture");
+        vehicle_data.display_name = MOCK_CAR_NAME.to_string();
+        vehicle_data
+    }
+
+    #[test]
+    fn should_follow_the_vehicle_through_its_lifecycle() {
+        let client = FakeTeslaApiClient::new();
+        let config = PollerConfig::default();
+        let mut previous = PreviousState::default();
+        let labels = [MOCK_CAR_NAME, "1"];
+        let car_state = || CAR_STATE_GAUGE.with_label_values(&labels).get();
+        let is_online = || CAR_ONLINE_GAUGE.with_label_values(&labels).get();
+        let polls = || (POLL_ATTEMPTS_COUNTER.with_label_values(&labels).get(),
+                        POLL_SUCCESSES_COUNTER.with_label_values(&labels).get());
+        let polls_before = polls();
+
+        // Offline: wake it
+        client.push_vehicle(vehicle("asleep"));
+        poll_once(&client, &1, &config, &mut previous);
+        assert_eq!(client.take_calls(), vec!["fetch_vehicle", "wake_vehicle_poll"]);
+        assert_eq!((car_state(), is_online()), (0, 1));
+        assert_eq!(previous.duration, Duration::from_secs(POLL_UNKNOWN_SECS));
+
+        // Parked
+        push(&client, vehicle_data());
+        poll_once(&client, &1, &config, &mut previous);
+        assert_eq!(client.take_calls(), vec!["fetch_vehicle", "fetch_vehicle_data"]);
+        assert_eq!((car_state(), is_online()), (1, 1));
+        assert_eq!(previous.duration, Duration::from_secs(POLL_PARKED_SECS));
+
+        // Charging
+        let mut charging = vehicle_data();
+        charging.charge_state.charging_state = ChargingState::Charging;
+        charging.drive_state.timestamp += 1;
+        push(&client, charging);
+        poll_once(&client, &1, &config, &mut previous);
+        assert_eq!(client.take_calls(), vec!["fetch_vehicle", "fetch_vehicle_data"]);
+        assert_eq!(car_state(), 2);
+        assert_eq!(previous.duration, Duration::from_secs(POLL_CHARGING_SECS));
+
+        // Driving
+        let mut driving = vehicle_data();
+        driving.drive_state.shift_state = Some(ShiftState::Drive);
+        driving.drive_state.speed = Some(42.0);
+        driving.drive_state.timestamp += 2;
+        push(&client, driving);
+        poll_once(&client, &1, &config, &mut previous);
+        assert_eq!(client.take_calls(), vec!["fetch_vehicle", "fetch_vehicle_data"]);
+        assert_eq!(car_state(), 3);
+        assert_eq!(previous.duration, Duration::from_secs(POLL_DRIVING_SECS));
+
+        // Parked again
+        let mut parked = vehicle_data();
+        parked.drive_state.timestamp += 3;
+        push(&client, parked);
+        poll_once(&client, &1, &config, &mut previous);
+        assert_eq!(client.take_calls(), vec!["fetch_vehicle", "fetch_vehicle_data"]);
+        assert_eq!(car_state(), 1);
+        assert_eq!(previous.duration, Duration::from_secs(POLL_PARKED_SECS));
+
+        // Offline while parked: let it sleep
+        client.push_vehicle(vehicle("asleep"));
+        poll_once(&client, &1, &config, &mut previous);
+        assert_eq!(client.take_calls(), vec!["fetch_vehicle"]);
+        assert_eq!((car_state(), is_online()), (1, 0));
+        assert_eq!(previous.duration, Duration::from_secs(POLL_PARKED_SECS));
+
+        // Waking and letting it sleep fetch no data
+        assert_eq!(polls(), (polls_before.0 + 6.0, polls_before.1 + 4.0));
+    }
+
+    #[test]
+    fn should_count_failed_polls_before_the_vehicle_name_is_known() {
+        let client = FakeTeslaApiClient::new();
+        let attempts = || POLL_ATTEMPTS_COUNTER.with_label_values(&["7", "1"]).get();
+        let attempts_before = attempts();
+
+        assert_eq!(poll_once(&client, &7, &PollerConfig::default(), &mut PreviousState::default()), None);
+        assert_eq!(attempts(), attempts_before + 1.0);
+    }
+
+    #[test]
+    fn should_reset_stale_vehicle_data_without_faking_values() {
+        let mut vehicle_data = vehicle_data();
+        vehicle_data.display_name = "Stale Auto".to_string();
+        let labels = [vehicle_data.display_name.as_str(), "1"];
+        record(&vehicle_data, "1");
+        let native_type = vehicle_data.drive_state.native_type_label();
+
+        reset_vehicle_data(&vehicle_data.display_name, "1", native_type);
+
+        assert_eq!(BATTERY_LEVEL_GAUGE.with_label_values(&labels).get(), 0);
+        assert_eq!(SHIFT_VALUE_GAUGE.with_label_values(&labels).get(), SHIFT_STATE_UNKNOWN_VALUE);
+        assert!(GEO_LAT_WGS84_GAUGE.remove_label_values(&labels).is_err());
+        assert!(GEO_LAT_GAUGE.remove_label_values(&[labels[0], native_type, "1"]).is_err());
+    }
+
+    #[test]
+    fn should_keep_same_named_vehicles_of_different_accounts_apart() {
+        let mut first = vehicle_data();
+        first.display_name = "Shared Auto".to_string();
+        first.charge_state.battery_level = 40;
+        let mut second = first.clone();
+        second.charge_state.battery_level = 80;
+
+        record(&first, "1");
+        record(&second, "2");
+
+        assert_eq!(BATTERY_LEVEL_GAUGE.with_label_values(&["Shared Auto", "1"]).get(), 40);
+        assert_eq!(BATTERY_LEVEL_GAUGE.with_label_values(&["Shared Auto", "2"]).get(), 80);
+    }
+
+    static WARNINGS: Lazy<Mutex<Vec<String>>> = Lazy::new(Default::default);
+
+    /// Collects the warnings into `WARNINGS`, the tests share one logger.
+    struct WarningLogger;
+
+    impl log::Log for WarningLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                WARNINGS.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_warnings() {
+        static LOGGER: WarningLogger = WarningLogger;
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(log::LevelFilter::Warn);
+        }
+    }
+
+    /// Records the car state changes of `car_name`, the outputs are shared with the other tests.
+    struct RecordingOutput {
+        car_name: &'static str,
+        changes: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Output for RecordingOutput {
+        fn publish(&self, _vehicle_data: &VehicleData) -> Result<()> {
+            Ok(())
+        }
+
+        fn publish_car_state(&self, car_name: &str, previous_state: &CarState, car_state: &CarState) -> Result<()> {
+            if car_name == self.car_name {
+                self.changes.lock().unwrap().push((previous_state.to_string(), car_state.to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_keep_polling_when_the_webhook_fails() {
+        capture_warnings();
+        // Nothing listens on port 0, so every call fails to connect
+        OUTPUTS.write().expect("Outputs lock is poisoned").push(Box::new(WebhookOutput::new("http://127.0.0.1:0/hook", None)));
+        let client = FakeTeslaApiClient::new();
+        let config = PollerConfig::default();
+        let mut previous = PreviousState::default();
+        let mut parked = vehicle_data();
+        parked.display_name = "Webhook Auto".to_string();
+        let car_state = || CAR_STATE_GAUGE.with_label_values(&["Webhook Auto", "1"]).get();
+
+        push(&client, parked.clone());
+        assert_eq!(poll_once(&client, &1, &config, &mut previous), Some("Webhook Auto".to_string()));
+        assert_eq!(car_state(), 1);
+
+        let mut driving = parked.clone();
+        driving.drive_state.shift_state = Some(ShiftState::Drive);
+        driving.drive_state.speed = Some(42.0);
+        driving.drive_state.timestamp += 1;
+        push(&client, driving);
+        assert_eq!(poll_once(&client, &1, &config, &mut previous), Some("Webhook Auto".to_string()));
+        assert_eq!(car_state(), 3);
+        assert_eq!(previous.duration, Duration::from_secs(POLL_DRIVING_SECS));
+
+        parked.drive_state.timestamp += 2;
+        push(&client, parked);
+        assert_eq!(poll_once(&client, &1, &config, &mut previous), Some("Webhook Auto".to_string()));
+        assert_eq!(car_state(), 1);
+
+        let warnings = WARNINGS.lock().unwrap();
+        for car_state in &["Parked", "Driving"] {
+            let prefix = format!("Failed to publish car state: Vehicle=\"Webhook Auto\" CarState=\"{}\"", car_state);
+            assert!(warnings.iter().any(|warning| warning.starts_with(&prefix)), "No warning {}", prefix);
+        }
+    }
+
+    #[test]
+    fn should_only_publish_car_state_when_its_variant_changes() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        OUTPUTS.write().expect("Outputs lock is poisoned").push(Box::new(RecordingOutput {
+            car_name: "Recording Auto",
+            changes: changes.clone(),
+        }));
+        let client = FakeTeslaApiClient::new();
+        let config = PollerConfig::default();
+        let mut previous = PreviousState::default();
+        let mut parked = vehicle_data();
+        parked.display_name = "Recording Auto".to_string();
+        let mut charging = parked.clone();
+        charging.charge_state.charging_state = ChargingState::Charging;
+
+        for (offset, vehicle_data) in [&parked, &parked, &charging, &charging].iter().enumerate() {
+            let mut vehicle_data = (*vehicle_data).clone();
+            vehicle_data.drive_state.timestamp += offset as i64;
+            push(&client, vehicle_data);
+            poll_once(&client, &1, &config, &mut previous);
+        }
+
+        assert_eq!(*changes.lock().unwrap(), vec![
+            ("Unknown".to_string(), "Parked".to_string()),
+            ("Parked".to_string(), "Charging".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn should_collect_vehicle_metrics_until_stopped() {
+        let client = FakeTeslaApiClient::new();
+        let mut parked = vehicle_data();
+        parked.display_name = "Fake Auto".to_string();
+        push(&client, parked);
+
+        let stop = StopSignal::new();
+        let (sender, receiver) = mpsc::channel();
+        let collector = {
+            let (client, stop) = (client.clone(), stop.clone());
+            thread::spawn(move || {
+                let health = PollerHealth::new(Duration::from_secs(60));
+                collect_vehicle_metrics(client, &2, 1, &PollerConfig::default(), stop, &health, Some(&sender))
+            })
+        };
+
+        assert_eq!(receiver.recv().map(|car_state| car_state.is_parked()), Ok(true));
+        stop.stop();
+        assert!(collector.join().expect("Collector panicked").is_ok());
+        assert_eq!(client.take_calls(), vec!["fetch_vehicle", "fetch_vehicle_data"]);
+    }
+
+    #[test]
+    fn should_count_charge_energy_across_sessions() {
+        let mut tracker = ChargeEnergyTracker::default();
+
+        assert_eq!(tracker.observe(30.0), 0.0);
+        assert_eq!(tracker.observe(32.5), 2.5);
+        assert_eq!(tracker.observe(31.0), 0.0);
+        assert_eq!(tracker.observe(33.0), 0.5);
+        assert_eq!(tracker.observe(0.5), 0.5);
+        assert_eq!(tracker.observe(1.5), 1.0);
+    }
+
+    #[test]
+    fn should_count_time_sentry_mode_was_active() {
+        let mut tracker = SentryModeTracker::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(tracker.observe_at(true, at(0)), Duration::from_secs(0));
+        assert_eq!(tracker.observe_at(true, at(30)), Duration::from_secs(30));
+        assert_eq!(tracker.observe_at(true, at(90)), Duration::from_secs(60));
+        assert_eq!(tracker.observe_at(false, at(120)), Duration::from_secs(0));
+        assert_eq!(tracker.observe_at(true, at(300)), Duration::from_secs(0));
+        assert_eq!(tracker.observe_at(true, at(330)), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn should_derive_hourly_api_call_rate_from_totals() {
+        let mut rate = ApiCallRate::default();
+        let start = Instant::now();
+        let at = |mins: u64| start + Duration::from_secs(mins * 60);
+        let totals = |calls: f64| [((MOCK_CAR_NAME.to_string(), "1".to_string()), calls)].iter().cloned().collect::<HashMap<_, _>>();
+
+        assert_eq!(rate.observe_at(at(0), totals(10.0)), HashMap::new());
+        assert_eq!(rate.observe_at(at(30), totals(40.0)), totals(60.0));
+        assert_eq!(rate.observe_at(at(60), totals(70.0)), totals(60.0));
+        assert_eq!(rate.observe_at(at(90), totals(80.0)), totals(40.0));
+    }
+
+    #[test]
+    fn should_detect_repeated_timestamps_as_stale() {
+        let mut detector = StaleDataDetector::default();
+
+        for _ in 0..=STALE_RESPONSE_POLLS {
+            detector.observe(1);
+            assert!(!detector.is_stale());
+        }
+        detector.observe(1);
+        assert!(detector.is_stale());
+        assert!(!detector.should_back_off());
+
+        for _ in 0..STALE_RESPONSE_BACKOFF_POLLS {
+            detector.observe(1);
+        }
+        assert!(detector.should_back_off());
+
+        detector.observe(2);
+        assert!(!detector.is_stale());
+    }
+
+    #[test]
+    fn should_label_api_errors_by_type() {
+        let blocked = anyhow::Error::from(TeslaApiError::Blocked("blocked".to_string())).context("Failed to fetch vehicle");
+        assert_eq!(api_error_type(&blocked), "blocked");
+        assert_eq!(api_error_type(&TeslaApiError::RateLimited(Duration::from_secs(30)).into()), "rate_limited");
+        assert_eq!(api_error_type(&anyhow!("Failed to read response")), "other");
+
+        let rate_limited = anyhow::Error::from(TeslaApiError::RateLimited(Duration::from_secs(90))).context("Failed to fetch vehicle");
+        assert_eq!(rate_limit_delay(&rate_limited), Some(Duration::from_secs(90)));
+        assert_eq!(rate_limit_delay(&blocked), None);
+    }
+
+    #[test]
+    fn should_stop_sleeping_jobs_on_shutdown() {
+        let mut jobs = JobHandles::default();
+        let stop = jobs.get_stop();
+        jobs.add_handle(thread::spawn(move || {
+            stop.sleep(Duration::from_secs(60));
+        }));
+
+        let started = Instant::now();
+        jobs.shutdown();
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn should_wait_for_the_other_jobs_when_one_panicked() {
+        let mut jobs = JobHandles::default();
+        let (sender, receiver) = mpsc::channel();
+        jobs.add_handle(thread::spawn(|| panic!("Job failed")));
+        jobs.add_handle(thread::spawn(move || sender.send(()).unwrap()));
+
+        jobs.wait();
+        assert!(receiver.try_recv().is_ok());
+    }
+
+    #[test]
+    fn should_be_healthy_while_a_vehicle_was_polled_recently() {
+        let health = PollerHealth::new(Duration::from_secs(60));
+        assert!(!health.report().healthy);
+
+        health.record_success(1, Instant::now() - Duration::from_secs(120));
+        assert!(!health.report().healthy);
+
+        health.record_success(2, Instant::now());
+        assert!(health.report().healthy);
+        assert_eq!(health.report().last_success_secs.get(&1), Some(&120));
+
+        health.remove(&2);
+        assert!(!health.report().healthy);
+    }
+
+    #[test]
+    fn should_read_poll_intervals_within_range() {
+        let lookup = |driving: &'static str| move |name: &str| match name {
+            "POLL_INTERVAL_DRIVING" => Some(driving.to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            PollIntervals::from_lookup(lookup("10")).ok(),
+            Some(PollIntervals { driving_secs: 10, ..PollIntervals::default() })
+        );
+        for invalid in ["0", "3601", "fast"] {
+            assert_eq!(
+                PollIntervals::from_lookup(lookup(invalid)).map_err(|err| err.to_string()),
+                Err(format!("POLL_INTERVAL_DRIVING=\"{}\" must be a number of seconds between 1 and 3600", invalid))
+            );
+        }
+
+        let alias = |name: &str| match name {
+            "TESLA_POLL_PARKED_SECS" => Some("300".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            PollIntervals::from_lookup(alias).ok(),
+            Some(PollIntervals { parked_secs: 300, ..PollIntervals::default() })
+        );
+    }
+
+    #[test]
+    fn should_filter_vehicles_by_name_or_vin() {
+        let mock = vehicle("online");
+        let other = Vehicle { display_name: "Other Auto".to_string(), vin: "5YJ3E1EA4KF311487".to_string(), ..vehicle("online") };
+        let filter = |allowlist: &[&str], denylist: &[&str]| VehicleFilter {
+            allowlist: allowlist.iter().map(|entry| entry.to_string()).collect(),
+            denylist: denylist.iter().map(|entry| entry.to_string()).collect(),
+        };
+
+        assert!(VehicleFilter::default().allows(&mock));
+        assert!(filter(&["mock auto"], &[]).allows(&mock));
+        assert!(!filter(&["mock auto"], &[]).allows(&other));
+        assert!(filter(&["5YJ3E1EA4KF311487"], &[]).allows(&other));
+        assert!(!filter(&["Mock Auto"], &["Mock Auto"]).allows(&mock));
+        assert!(!filter(&[], &["5yj3e1ea4kf311487"]).allows(&other));
+    }
+
+    #[test]
+    fn should_wait_for_configured_poll_intervals() -> Result<()> {
+        let vehicle_data: VehicleData = serde_json::from_str(include_str!("../tests/fixtures/vehicle_data.json"))?;
+        let config = PollerConfig::builder()
+            .poll_intervals(PollIntervals { parked_secs: 120, ..PollIntervals::default() })
+            .build();
+
+        assert_eq!(CarState::Parked(vehicle_data.clone()).wait_with(&config), Duration::from_secs(120));
+        assert_eq!(CarState::Driving(vehicle_data).wait_with(&config), Duration::from_secs(POLL_DRIVING_SECS));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_poll_parked_vehicles_with_low_battery_less_often() -> Result<()> {
+        let mut vehicle_data: VehicleData = serde_json::from_str(include_str!("../tests/fixtures/vehicle_data.json"))?;
+        let config = PollerConfig::default();
+
+        assert_eq!(CarState::Parked(vehicle_data.clone()).wait_with(&config), Duration::from_secs(POLL_PARKED_SECS));
+
+        vehicle_data.charge_state.battery_level = 9;
+        assert_eq!(CarState::Parked(vehicle_data.clone()).wait_with(&config), Duration::from_secs(60));
+        assert_eq!(CarState::Driving(vehicle_data).wait_with(&config), Duration::from_secs(POLL_DRIVING_SECS));
+
+        Ok(())
     }
 }
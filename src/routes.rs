@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use rocket::get;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::State;
+use rocket_contrib::json::Json;
+use serde::Serialize;
+
+use crate::poller::{HealthReport, JobHandles, PollerHealth};
+
+/// The body of `GET /health` and `GET /ready`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProbeStatus {
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// `GET /health`, 200 as long as the process serves requests, for Kubernetes liveness probes.
+#[get("/health")]
+pub fn health() -> Json<ProbeStatus> {
+    Json(ProbeStatus { status: "ok", reason: None })
+}
+
+/// `GET /ready`, 200 once a vehicle collection thread is running and has polled its vehicle
+/// successfully, 503 otherwise, for Kubernetes readiness probes.
+#[get("/ready")]
+pub fn ready(jobs: State<Arc<JobHandles>>) -> status::Custom<Json<ProbeStatus>> {
+    let (status, body) = readiness(jobs.health());
+    status::Custom(status, Json(body))
+}
+
+fn readiness(health: &PollerHealth) -> (Status, ProbeStatus) {
+    if health.polled_vehicle_count() > 0 {
+        return (Status::Ok, ProbeStatus { status: "ready", reason: None });
+    }
+    let reason = "No vehicle collection thread has polled its vehicle successfully yet".to_string();
+    (Status::ServiceUnavailable, ProbeStatus { status: "not_ready", reason: Some(reason) })
+}
+
+/// `GET /healthz`, 200 while a vehicle collection thread is alive and has recently polled successfully,
+/// 503 otherwise.
+#[get("/healthz")]
+pub fn healthz(jobs: State<Arc<JobHandles>>) -> status::Custom<Json<HealthReport>> {
+    let report = jobs.health().report();
+    let status = if report.healthy { Status::Ok } else { Status::ServiceUnavailable };
+    status::Custom(status, Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn should_be_ready_once_a_vehicle_was_polled() {
+        let health = PollerHealth::new(Duration::from_secs(60));
+        let (status, body) = readiness(&health);
+        assert_eq!(status, Status::ServiceUnavailable);
+        assert_eq!(body.status, "not_ready");
+        assert!(body.reason.is_some());
+
+        // Ready even when the last poll is too old for /healthz
+        health.record_success(1, Instant::now() - Duration::from_secs(120));
+        assert_eq!(readiness(&health), (Status::Ok, ProbeStatus { status: "ready", reason: None }));
+
+        health.remove(&1);
+        assert_eq!(readiness(&health).0, Status::ServiceUnavailable);
+    }
+}
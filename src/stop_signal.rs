@@ -0,0 +1,63 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Tells background jobs to stop. Jobs waiting in [`StopSignal::sleep`] wake up right away
+/// instead of at the end of their interval, so shutdown doesn't wait for the longest poll interval.
+///
+/// Clones share the signal.
+#[derive(Debug, Clone, Default)]
+pub struct StopSignal {
+    inner: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl StopSignal {
+    pub fn new() -> Self {
+        StopSignal::default()
+    }
+
+    pub fn stop(&self) {
+        let (stopped, condvar) = &*self.inner;
+        *stopped.lock().expect("Stop signal lock is poisoned") = true;
+        condvar.notify_all();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        *self.inner.0.lock().expect("Stop signal lock is poisoned")
+    }
+
+    /// Sleeps for `duration`, returns `false` as soon as the signal is stopped.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let (stopped, condvar) = &*self.inner;
+        let stopped = stopped.lock().expect("Stop signal lock is poisoned");
+        let (stopped, _) = condvar
+            .wait_timeout_while(stopped, duration, |stopped| !*stopped)
+            .expect("Stop signal lock is poisoned");
+        !*stopped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn should_wake_sleeping_threads_on_stop() {
+        let stop = StopSignal::new();
+        assert!(stop.sleep(Duration::from_millis(10)));
+
+        let sleeper = {
+            let stop = stop.clone();
+            thread::spawn(move || stop.sleep(Duration::from_secs(60)))
+        };
+        let started = Instant::now();
+        thread::sleep(Duration::from_millis(50));
+        stop.stop();
+
+        assert!(!sleeper.join().unwrap());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(stop.is_stopped());
+    }
+}
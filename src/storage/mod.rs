@@ -0,0 +1,202 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use once_cell::sync::Lazy;
+use rocket::get;
+use rocket_contrib::json::Json;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::tesla_api_client::dtos::VehicleData;
+
+const DEFAULT_RETENTION_MINUTES: u64 = 60;
+const DEFAULT_HISTORY_MINUTES: u64 = 30;
+
+/// The recorded vehicle data, kept for `RETENTION_MINUTES` and served by `GET /history`.
+pub static TIME_SERIES_STORE: Lazy<TimeSeriesStore> = Lazy::new(TimeSeriesStore::from_env);
+
+/// The values of one `record()` call that are worth plotting over time.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MetricSnapshot {
+    /// Milliseconds since the epoch, as reported by the vehicle.
+    pub timestamp: i64,
+    pub display_name: String,
+    pub vin: String,
+    pub battery_level: i32,
+    pub battery_range: f64,
+    pub charge_rate: f64,
+    pub charger_power: f64,
+    pub charging_state: String,
+    pub speed: f64,
+    pub power: f64,
+    pub odometer: f64,
+    pub inside_temp: f64,
+    pub outside_temp: f64,
+}
+
+impl From<&VehicleData> for MetricSnapshot {
+    fn from(vehicle_data: &VehicleData) -> Self {
+        MetricSnapshot {
+            timestamp: vehicle_data.charge_state.timestamp,
+            display_name: vehicle_data.display_name.clone(),
+            vin: vehicle_data.extra.get("vin").and_then(Value::as_str).unwrap_or_default().to_string(),
+            battery_level: vehicle_data.charge_state.battery_level,
+            battery_range: vehicle_data.charge_state.battery_range,
+            charge_rate: vehicle_data.charge_state.charge_rate,
+            charger_power: vehicle_data.charge_state.charger_power,
+            charging_state: vehicle_data.charge_state.charging_state.to_string(),
+            speed: vehicle_data.drive_state.speed.unwrap_or(0.0_f64),
+            power: vehicle_data.drive_state.power,
+            odometer: vehicle_data.vehicle_state.odometer,
+            inside_temp: vehicle_data.climate_state.inside_temp,
+            outside_temp: vehicle_data.climate_state.outside_temp,
+        }
+    }
+}
+
+/// One line status, e.g. `Model 3 (VIN: 5YJ3E1EA4KF311487): Charging at 7.2kW, 85% battery, 280mi range, 22°C inside`.
+impl Display for MetricSnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (VIN: {}): ", self.display_name, self.vin)?;
+        if self.charging_state == "Charging" {
+            write!(f, "Charging at {:.1}kW", self.charger_power)?;
+        } else if self.speed > 0.0 {
+            write!(f, "Driving at {:.0}mph", self.speed)?;
+        } else {
+            write!(f, "{}", self.charging_state)?;
+        }
+        write!(f, ", {}% battery, {:.0}mi range, {:.0}°C inside", self.battery_level, self.battery_range, self.inside_temp)
+    }
+}
+
+/// In-process time series per vehicle, so recent history can be viewed without an external TSDB.
+/// Snapshots older than `retention` are dropped whenever a new one is added.
+pub struct TimeSeriesStore {
+    retention: Duration,
+    series: Mutex<HashMap<i64, VecDeque<(Instant, MetricSnapshot)>>>,
+}
+
+impl TimeSeriesStore {
+    pub fn new(retention: Duration) -> Self {
+        TimeSeriesStore { retention, series: Mutex::new(HashMap::new()) }
+    }
+
+    /// Retains `RETENTION_MINUTES` minutes, 60 by default.
+    pub fn from_env() -> Self {
+        let minutes = match env::var("RETENTION_MINUTES") {
+            Err(_) => DEFAULT_RETENTION_MINUTES,
+            Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+                warn!("Ignoring invalid RETENTION_MINUTES=\"{}\", using {} minutes", value, DEFAULT_RETENTION_MINUTES);
+                DEFAULT_RETENTION_MINUTES
+            }),
+        };
+        TimeSeriesStore::new(Duration::from_secs(minutes * 60))
+    }
+
+    pub fn record(&self, vehicle_id: i64, snapshot: MetricSnapshot) {
+        self.record_at(vehicle_id, Instant::now(), snapshot);
+    }
+
+    fn record_at(&self, vehicle_id: i64, at: Instant, snapshot: MetricSnapshot) {
+        let mut series = self.series.lock().expect("Time series lock is poisoned");
+        let snapshots = series.entry(vehicle_id).or_default();
+        snapshots.push_back((at, snapshot));
+
+        while let Some((recorded_at, _)) = snapshots.front() {
+            if at.saturating_duration_since(*recorded_at) <= self.retention {
+                break;
+            }
+            snapshots.pop_front();
+        }
+    }
+
+    /// The most recent snapshot of every vehicle, ordered by vehicle id.
+    pub fn latest(&self) -> Vec<MetricSnapshot> {
+        let series = self.series.lock().expect("Time series lock is poisoned");
+        let mut latest: Vec<(&i64, &MetricSnapshot)> = series
+            .iter()
+            .filter_map(|(vehicle_id, snapshots)| snapshots.back().map(|(_, snapshot)| (vehicle_id, snapshot)))
+            .collect();
+        latest.sort_by_key(|(vehicle_id, _)| **vehicle_id);
+        latest.into_iter().map(|(_, snapshot)| snapshot.clone()).collect()
+    }
+
+    /// The snapshots of the last `window`, oldest first.
+    pub fn history(&self, vehicle_id: i64, window: Duration) -> Vec<MetricSnapshot> {
+        let series = self.series.lock().expect("Time series lock is poisoned");
+        series
+            .get(&vehicle_id)
+            .map(|snapshots| {
+                snapshots
+                    .iter()
+                    .filter(|(recorded_at, _)| recorded_at.elapsed() <= window)
+                    .map(|(_, snapshot)| snapshot.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// `GET /history/<vehicle_id>?minutes=30`, the vehicle's snapshots of the last `minutes` as JSON.
+#[get("/<vehicle_id>?<minutes>")]
+pub fn history(vehicle_id: i64, minutes: Option<u64>) -> Json<Vec<MetricSnapshot>> {
+    let window = Duration::from_secs(minutes.unwrap_or(DEFAULT_HISTORY_MINUTES) * 60);
+    Json(TIME_SERIES_STORE.history(vehicle_id, window))
+}
+
+/// `GET /metrics/summary`, a human-readable status line per vehicle.
+#[get("/summary")]
+pub fn summary() -> String {
+    let lines: Vec<String> = TIME_SERIES_STORE.latest().iter().map(MetricSnapshot::to_string).collect();
+    if lines.is_empty() {
+        return "No vehicle data recorded yet\n".to_string();
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn should_drop_snapshots_older_than_retention() -> Result<()> {
+        let vehicle_data: VehicleData = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        let store = TimeSeriesStore::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        store.record_at(1, now, MetricSnapshot { battery_level: 80, ..MetricSnapshot::from(&vehicle_data) });
+        store.record_at(1, now + Duration::from_secs(30), MetricSnapshot { battery_level: 81, ..MetricSnapshot::from(&vehicle_data) });
+        store.record_at(1, now + Duration::from_secs(90), MetricSnapshot { battery_level: 82, ..MetricSnapshot::from(&vehicle_data) });
+
+        let battery_levels: Vec<i32> = store
+            .history(1, Duration::from_secs(3600))
+            .iter()
+            .map(|snapshot| snapshot.battery_level)
+            .collect();
+        assert_eq!(battery_levels, vec![81, 82]);
+        assert_eq!(store.history(2, Duration::from_secs(3600)), vec![]);
+        assert_eq!(store.latest().iter().map(|snapshot| snapshot.battery_level).collect::<Vec<_>>(), vec![82]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_summarize_snapshot() -> Result<()> {
+        let vehicle_data: VehicleData = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        let snapshot = MetricSnapshot::from(&vehicle_data);
+
+        assert_eq!(
+            MetricSnapshot { charging_state: "Charging".to_string(), charger_power: 7.2, ..snapshot }.to_string(),
+            "Bellwood Auto (VIN: 5YJ3E1EA4KF311487): Charging at 7.2kW, 87% battery, 208mi range, 11°C inside"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use std::env;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use log::{error, info};
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::tesla_api_client::dtos::VehicleData;
+
+/// The last known vehicle data, kept in the SQLite database at `STATE_DB_PATH` if it is set.
+pub static STATE_STORE: Lazy<Option<StateStore>> = Lazy::new(|| match StateStore::from_env() {
+    Ok(state_store) => state_store,
+    Err(err) => {
+        error!("Failed to open state database, vehicle data won't be restored on restart: {:#}", err);
+        None
+    }
+});
+
+/// The vehicle data of the last successful poll of a vehicle.
+#[derive(Debug, Clone)]
+pub struct StoredState {
+    pub vehicle_data: VehicleData,
+    /// When `vehicle_data` was fetched, seconds since the epoch.
+    pub updated_at: i64,
+    /// When the vehicle was last seen, including polls that found it asleep or offline.
+    pub seen_at: i64,
+}
+
+/// Persists the last successful `VehicleData` of every vehicle, so the gauges start from the last
+/// known values after a restart instead of zero.
+pub struct StateStore {
+    connection: Mutex<Connection>,
+}
+
+impl StateStore {
+    /// `None` unless `STATE_DB_PATH` is set, e.g. `/data/state.db`.
+    pub fn from_env() -> Result<Option<Self>> {
+        match env::var("STATE_DB_PATH") {
+            Ok(path) => {
+                let state_store = StateStore::open(Path::new(&path))?;
+                info!("Opened state database: Path=\"{}\"", path);
+                Ok(Some(state_store))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)
+            .with_context(|| format!("Failed to open state database {}", path.display()))?;
+        StateStore::with_connection(connection)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        StateStore::with_connection(Connection::open_in_memory().context("Failed to open in-memory state database")?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self> {
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS vehicle_state (
+                    vehicle_id INTEGER PRIMARY KEY,
+                    vehicle_data TEXT NOT NULL,
+                    updated_at INTEGER NOT NULL,
+                    seen_at INTEGER NOT NULL
+                )",
+            )
+            .context("Failed to create the vehicle_state table")?;
+        Ok(StateStore { connection: Mutex::new(connection) })
+    }
+
+    /// Replaces the stored vehicle data with data fetched just now.
+    pub fn save(&self, vehicle_id: i64, vehicle_data: &VehicleData) -> Result<()> {
+        let json = serde_json::to_string(vehicle_data).context("Failed to serialize vehicle data")?;
+        let now = now_secs();
+        self.connection()
+            .execute(
+                "INSERT INTO vehicle_state (vehicle_id, vehicle_data, updated_at, seen_at) VALUES (?1, ?2, ?3, ?3)
+                 ON CONFLICT (vehicle_id) DO UPDATE SET vehicle_data = ?2, updated_at = ?3, seen_at = ?3",
+                params![vehicle_id, json, now],
+            )
+            .with_context(|| format!("Failed to save vehicle state: VehicleId=\"{}\"", vehicle_id))?;
+        Ok(())
+    }
+
+    /// Records that the vehicle was seen without fetching its data, e.g. while it is asleep, and keeps
+    /// the stored data and its `updated_at`.
+    pub fn mark_seen(&self, vehicle_id: i64) -> Result<()> {
+        self.connection()
+            .execute("UPDATE vehicle_state SET seen_at = ?1 WHERE vehicle_id = ?2", params![now_secs(), vehicle_id])
+            .with_context(|| format!("Failed to update vehicle state: VehicleId=\"{}\"", vehicle_id))?;
+        Ok(())
+    }
+
+    pub fn load(&self, vehicle_id: i64) -> Result<Option<StoredState>> {
+        let row: Option<(String, i64, i64)> = self.connection()
+            .query_row(
+                "SELECT vehicle_data, updated_at, seen_at FROM vehicle_state WHERE vehicle_id = ?1",
+                params![vehicle_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .with_context(|| format!("Failed to load vehicle state: VehicleId=\"{}\"", vehicle_id))?;
+
+        match row {
+            None => Ok(None),
+            Some((json, updated_at, seen_at)) => {
+                let vehicle_data = serde_json::from_str(&json)
+                    .with_context(|| format!("Invalid stored vehicle data: VehicleId=\"{}\"", vehicle_id))?;
+                Ok(Some(StoredState { vehicle_data, updated_at, seen_at }))
+            }
+        }
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection.lock().expect("State database lock is poisoned")
+    }
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_keep_vehicle_data_while_the_vehicle_sleeps() -> Result<()> {
+        let vehicle_data: VehicleData = serde_json::from_str(include_str!("../tests/fixtures/vehicle_data.json"))?;
+        let store = StateStore::open_in_memory()?;
+        assert!(store.load(1)?.is_none());
+
+        store.mark_seen(1)?;
+        assert!(store.load(1)?.is_none());
+
+        store.save(1, &vehicle_data)?;
+        store.mark_seen(1)?;
+        let stored = store.load(1)?.expect("Vehicle state wasn't stored");
+        assert_eq!(stored.vehicle_data.display_name, vehicle_data.display_name);
+        assert_eq!(stored.vehicle_data.charge_state.battery_level, vehicle_data.charge_state.battery_level);
+        assert!(stored.seen_at >= stored.updated_at);
+        assert!(store.load(2)?.is_none());
+
+        Ok(())
+    }
+}
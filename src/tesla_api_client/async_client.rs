@@ -0,0 +1,207 @@
+//! Async client for the Tesla Owner API, built with `--features async`.
+//!
+//! Mirrors the requests of the blocking [`TeslaApiClient`](super::TeslaApiClient) the poller needs,
+//! for embedding in an async service or polling many vehicles without one thread per vehicle. The
+//! DTOs and the mapping of responses to [`TeslaApiError`]s are shared with the blocking client.
+//!
+//! ```no_run
+//! use tesla_api_exporter::tesla_api_client::dtos::AuthToken;
+//! use tesla_api_exporter::tesla_api_client::TeslaApiClient;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = TeslaApiClient::builder(AuthToken::from_env()).build_async()?;
+//! for vehicle in client.fetch_vehicles().await? {
+//!     let data = client.fetch_vehicle_data(&vehicle.id).await?;
+//!     println!("{} is at {}%", vehicle.display_name, data.charge_state.battery_level);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::env;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use reqwest::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+
+use crate::tesla_api_client::dtos::{AuthToken, Reply, TeslaApiError, Vehicle, VehicleData};
+use crate::tesla_api_client::token_store::TokenStore;
+use crate::tesla_api_client::{map_response, retry, TeslaApiClientBuilder, USER_AGENT};
+
+#[derive(Debug, Clone)]
+pub struct TeslaApiClientAsync {
+    client: Client,
+    /// Owner API base URL without a trailing `/`.
+    api_url: String,
+    /// Auth API base URL without a trailing `/`.
+    auth_api_url: String,
+    /// Shared by every clone of the client, so a refreshed token is used everywhere.
+    auth_token: Arc<RwLock<AuthToken>>,
+    /// Receives every refreshed token when configured.
+    token_store: Option<Arc<dyn TokenStore>>,
+}
+
+impl TeslaApiClientBuilder {
+    /// Builds a [`TeslaApiClientAsync`] with the base URLs, token store and timeouts of this builder.
+    ///
+    /// The async client doesn't retry requests, a configured retry policy is ignored. Fails when
+    /// `TESLA_CERT_PIN_SHA256` is set, certificate pinning is only supported by the blocking client.
+    pub fn build_async(self) -> Result<TeslaApiClientAsync> {
+        if env::var("TESLA_CERT_PIN_SHA256").is_ok() {
+            return Err(anyhow!("TESLA_CERT_PIN_SHA256 is not supported by the async client"));
+        }
+
+        let client = Client::builder()
+            .timeout(self.read_timeout + self.write_timeout)
+            .connect_timeout(self.write_timeout)
+            .user_agent(USER_AGENT)
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        let auth_token = match &self.token_store {
+            None => self.auth_token,
+            Some(token_store) => match token_store.load() {
+                Ok(auth_token) => auth_token,
+                Err(err) => {
+                    info!("Using the configured auth token, no stored token: {:#}", err);
+                    self.auth_token
+                }
+            },
+        };
+
+        Ok(TeslaApiClientAsync {
+            client,
+            api_url: self.api_url.trim_end_matches('/').to_string(),
+            auth_api_url: self.auth_api_url.trim_end_matches('/').to_string(),
+            auth_token: Arc::new(RwLock::new(auth_token)),
+            token_store: self.token_store,
+        })
+    }
+}
+
+impl TeslaApiClientAsync {
+    /// Exchanges the refresh token for a new access token and replaces the current one, see
+    /// [`TeslaApiClient::refresh_auth`](super::TeslaApiClient::refresh_auth).
+    pub async fn refresh_auth(&self) -> Result<()> {
+        let refresh_token = self.read_auth_token().refresh_token.clone();
+        let api_url = format!("{api_url}/oauth2/v3/token", api_url = self.auth_api_url);
+        let result = self.client.post(&api_url)
+            .json(&serde_json::json!({
+                "grant_type": "refresh_token",
+                "client_id": "ownerapi",
+                "scope": "openid email offline_access",
+                "refresh_token": &refresh_token,
+            }))
+            .send()
+            .await;
+
+        let auth_token = handle_response::<AuthToken>(result).await
+            .context("Failed to refresh auth token")?
+            .with_expiry_from_now();
+        if let Some(token_store) = &self.token_store {
+            if let Err(err) = token_store.save(&auth_token) {
+                warn!("Failed to save refreshed auth token: {:#}", err);
+            }
+        }
+        *self.auth_token.write().expect("Auth token lock is poisoned") = auth_token;
+        Ok(())
+    }
+
+    /// Whether the access token is expired or about to expire, `false` when the expiry is unknown.
+    pub fn is_auth_expired(&self) -> bool {
+        self.read_auth_token().is_expired()
+    }
+
+    fn read_auth_token(&self) -> RwLockReadGuard<'_, AuthToken> {
+        self.auth_token.read().expect("Auth token lock is poisoned")
+    }
+
+    /// Fetches all vehicles of the account without waking them, see
+    /// [`TeslaApiClient::fetch_vehicles`](super::TeslaApiClient::fetch_vehicles).
+    pub async fn fetch_vehicles(&self) -> Result<Vec<Vehicle>> {
+        let api_url = format!("{api_url}/api/1/vehicles", api_url = self.api_url);
+        let result = self.call_with_refresh(|| self.client.get(&api_url)).await;
+
+        let reply = handle_response::<Reply<Vec<Vehicle>>>(result).await
+            .context("Failed to fetch vehicles")?;
+        Ok(reply.response)
+    }
+
+    /// Fetches the charge, climate, drive and vehicle state of a vehicle, see
+    /// [`TeslaApiClient::fetch_vehicle_data`](super::TeslaApiClient::fetch_vehicle_data).
+    ///
+    /// Unlike the blocking client, concurrent calls for the same vehicle each send a request.
+    pub async fn fetch_vehicle_data(&self, vehicle_id: &i64) -> Result<VehicleData> {
+        let api_url = format!(
+            "{api_url}/api/1/vehicles/{id}/vehicle_data",
+            api_url = self.api_url,
+            id = vehicle_id
+        );
+        let result = self.call_with_refresh(|| self.client.get(&api_url)).await;
+
+        let reply = handle_response::<Reply<VehicleData>>(result).await
+            .with_context(|| format!("Failed to fetch vehicle data for vehicle {}", vehicle_id))?;
+        Ok(reply.response)
+    }
+
+    /// Sends a single `wake_up` request and returns the vehicle as reported at that moment, see
+    /// [`TeslaApiClient::wake_vehicle`](super::TeslaApiClient::wake_vehicle).
+    pub async fn wake_vehicle(&self, vehicle_id: &i64) -> Result<Vehicle> {
+        let api_url = format!(
+            "{api_url}/api/1/vehicles/{id}/wake_up",
+            api_url = self.api_url,
+            id = vehicle_id
+        );
+        let result = self.call_with_refresh(|| self.client.post(&api_url)).await;
+
+        let reply = handle_response::<Reply<Vehicle>>(result).await
+            .with_context(|| format!("Failed to wake vehicle {}", vehicle_id))?;
+        Ok(reply.response)
+    }
+
+    /// Sends the request built by `request` with the access token, refreshing it first when it is
+    /// about to expire. When the token is rejected with a 401 it is refreshed and the request
+    /// replayed once.
+    ///
+    /// Concurrent requests may each refresh an expiring token.
+    async fn call_with_refresh(&self, request: impl Fn() -> RequestBuilder) -> reqwest::Result<Response> {
+        if self.is_auth_expired() {
+            match self.refresh_auth().await {
+                Ok(_) => info!("Refreshed expiring auth token"),
+                Err(err) => warn!("Failed to refresh expiring auth token: {:#}", err),
+            }
+        }
+
+        let response = self.send(&request).await?;
+        if response.status().as_u16() != 401 {
+            return Ok(response);
+        }
+        if let Err(err) = self.refresh_auth().await {
+            warn!("Failed to refresh rejected auth token: {:#}", err);
+            return Ok(response);
+        }
+        info!("Refreshed rejected auth token, retrying request");
+        self.send(&request).await
+    }
+
+    async fn send(&self, request: &impl Fn() -> RequestBuilder) -> reqwest::Result<Response> {
+        let access_token = self.read_auth_token().access_token.clone();
+        request().bearer_auth(access_token).send().await
+    }
+}
+
+async fn handle_response<T: DeserializeOwned>(result: reqwest::Result<Response>) -> Result<T> {
+    let response = match result {
+        Ok(response) => response,
+        Err(_) => return Err(TeslaApiError::Unknown.into()),
+    };
+    let status = response.status().as_u16();
+    let retry_after = response.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(retry::parse_retry_after);
+    let body = response.text().await.context("Failed to read response");
+    map_response(status, retry_after, body)
+}
@@ -0,0 +1,210 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::{info, warn};
+
+use crate::tesla_api_client::dtos::{TeslaApiError, Vehicle, VehicleData};
+use crate::tesla_api_client::{TeslaApiClient, TeslaApiClientTrait, VehicleDataResult, WakeResult};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_RECOVERY_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    /// Requests are sent.
+    Closed,
+    /// The API is considered down, requests fail with [`TeslaApiError::CircuitOpen`].
+    Open,
+    /// The recovery timeout passed, one probe request is sent to find out if the API is back.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Stops sending requests for `recovery_timeout` once `failure_threshold` consecutive requests
+/// failed, so an API outage doesn't make every polling thread hammer the API.
+///
+/// Clones share the circuit, so one breaker protects the requests of every vehicle. Errors about a
+/// single vehicle, e.g. [`TeslaApiError::VehicleUnavailable`], don't count as failures.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    client: TeslaApiClient,
+    failure_threshold: u32,
+    recovery_timeout: Duration,
+    circuit: Arc<Mutex<Circuit>>,
+}
+
+impl CircuitBreaker {
+    /// Opens after 5 consecutive failures and probes again after 60 seconds.
+    pub fn new(client: TeslaApiClient) -> Self {
+        CircuitBreaker::with_thresholds(client, DEFAULT_FAILURE_THRESHOLD, DEFAULT_RECOVERY_TIMEOUT)
+    }
+
+    pub fn with_thresholds(client: TeslaApiClient, failure_threshold: u32, recovery_timeout: Duration) -> Self {
+        CircuitBreaker {
+            client,
+            failure_threshold,
+            recovery_timeout,
+            circuit: Arc::new(Mutex::new(Circuit {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+            })),
+        }
+    }
+
+    /// The wrapped client, e.g. to refresh its token.
+    pub fn client(&self) -> &TeslaApiClient {
+        &self.client
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.lock().state
+    }
+
+    /// Sends `request` unless the circuit is open, returns [`TeslaApiError::CircuitOpen`] without
+    /// a request otherwise.
+    pub fn call<T>(&self, request: impl FnOnce(&TeslaApiClient) -> Result<T>) -> Result<T> {
+        self.before_request()?;
+        let result = request(&self.client);
+        self.after_request(result.as_ref().err());
+        result
+    }
+
+    fn before_request(&self) -> Result<()> {
+        let mut circuit = self.lock();
+        match circuit.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open if circuit.opened_at.is_none_or(|at| at.elapsed() >= self.recovery_timeout) => {
+                info!("Probing Tesla API after {:?} with the circuit open", self.recovery_timeout);
+                circuit.state = CircuitState::HalfOpen;
+                Ok(())
+            }
+            CircuitState::Open | CircuitState::HalfOpen => Err(TeslaApiError::CircuitOpen.into()),
+        }
+    }
+
+    fn after_request(&self, err: Option<&anyhow::Error>) {
+        let mut circuit = self.lock();
+        match err {
+            Some(err) if is_api_failure(err) => {
+                circuit.consecutive_failures += 1;
+                if circuit.state == CircuitState::HalfOpen || circuit.consecutive_failures >= self.failure_threshold {
+                    if circuit.state != CircuitState::Open {
+                        warn!("Opening Tesla API circuit: Failures=\"{}\" Waiting=\"{:?}\"",
+                              circuit.consecutive_failures, self.recovery_timeout);
+                    }
+                    circuit.state = CircuitState::Open;
+                    circuit.opened_at = Some(Instant::now());
+                }
+            }
+            _ => {
+                if circuit.state != CircuitState::Closed {
+                    info!("Closing Tesla API circuit, the API responded again");
+                }
+                circuit.state = CircuitState::Closed;
+                circuit.consecutive_failures = 0;
+                circuit.opened_at = None;
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Circuit> {
+        self.circuit.lock().expect("Circuit breaker lock is poisoned")
+    }
+}
+
+/// Whether `err` hints at an API problem rather than at a single vehicle.
+fn is_api_failure(err: &anyhow::Error) -> bool {
+    !matches!(
+        err.downcast_ref::<TeslaApiError>(),
+        Some(TeslaApiError::VehicleUnavailable())
+            | Some(TeslaApiError::WakeTimeout())
+            | Some(TeslaApiError::CommandNotSupported { .. })
+            | Some(TeslaApiError::CircuitOpen)
+    )
+}
+
+impl TeslaApiClientTrait for CircuitBreaker {
+    fn fetch_vehicle(&self, vehicle_id: &i64) -> Result<Vehicle> {
+        self.call(|client| client.fetch_vehicle(vehicle_id))
+    }
+
+    fn fetch_vehicles(&self) -> Result<Vec<Vehicle>> {
+        self.call(|client| client.fetch_vehicles())
+    }
+
+    fn fetch_vehicle_data(&self, vehicle_id: &i64) -> Result<VehicleData> {
+        self.call(|client| client.fetch_vehicle_data(vehicle_id))
+    }
+
+    fn fetch_all_vehicles_data(&self) -> Result<Vec<VehicleDataResult>> {
+        self.call(|client| client.fetch_all_vehicles_data())
+    }
+
+    fn wake_vehicle(&self, vehicle_id: &i64) -> Result<Vehicle> {
+        self.call(|client| client.wake_vehicle(vehicle_id))
+    }
+
+    fn wake_vehicle_poll_observed(&self, vehicle_id: &i64, on_attempt: &mut dyn FnMut()) -> Result<WakeResult> {
+        self.call(|client| client.wake_vehicle_poll_observed(vehicle_id, on_attempt))
+    }
+
+    /// Refreshes the token of the wrapped client whatever the state of the circuit.
+    fn refresh_auth(&self) -> Result<()> {
+        self.client.refresh_auth()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+
+    use anyhow::anyhow;
+
+    use crate::tesla_api_client::dtos::AuthToken;
+
+    use super::*;
+
+    fn breaker(recovery_timeout: Duration) -> CircuitBreaker {
+        let client = TeslaApiClient::builder(AuthToken {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            expires_in: None,
+            expires_at: None,
+        }).build().unwrap();
+        CircuitBreaker::with_thresholds(client, 2, recovery_timeout)
+    }
+
+    fn is_circuit_open(result: Result<()>) -> bool {
+        result.unwrap_err().downcast_ref::<TeslaApiError>() == Some(&TeslaApiError::CircuitOpen)
+    }
+
+    #[test]
+    fn should_open_after_consecutive_failures_and_probe_after_recovery_timeout() {
+        let breaker = breaker(Duration::from_millis(50));
+        let fail = |_: &TeslaApiClient| -> Result<()> { Err(TeslaApiError::Unknown.into()) };
+
+        assert!(breaker.call(|_| -> Result<()> { Err(TeslaApiError::VehicleUnavailable().into()) }).is_err());
+        assert!(breaker.call(fail).is_err());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.call(fail).is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(is_circuit_open(breaker.call(|_| Ok(()))));
+
+        sleep(Duration::from_millis(60));
+        assert!(breaker.call(|_| -> Result<()> { Err(anyhow!("still down")) }).is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(is_circuit_open(breaker.call(|_| Ok(()))));
+
+        sleep(Duration::from_millis(60));
+        assert!(breaker.call(|_| Ok(())).is_ok());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
@@ -1,8 +1,12 @@
-use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Deserializer, Serialize};
 use thiserror::Error;
 use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::time::{Duration, SystemTime};
 use serde_json::Value;
+use serde_with::{serde_as, DefaultOnNull, DisplayFromStr, NoneAsEmptyString, PickFirst};
 
 #[derive(Error, Debug, PartialEq)]
 pub enum TeslaApiError {
@@ -20,6 +24,32 @@ pub enum TeslaApiError {
     Unknown,
     #[error("Request was blocked: {0:?}")]
     Blocked(String),
+    #[error("Vehicle {vehicle} does not support the {command} command")]
+    CommandNotSupported { command: String, vehicle: String },
+    #[error("Tesla API circuit is open after repeated failures")]
+    CircuitOpen,
+    /// HTTP 429, holds how long the API asked to wait before the next request.
+    #[error("Too many requests, retry after {0:?}")]
+    RateLimited(Duration),
+}
+
+impl TeslaApiError {
+    /// The `error_type` label of `tesla_api_errors_total`.
+    pub fn error_type(&self) -> &'static str {
+        match self {
+            TeslaApiError::LoginFailure => "login_failure",
+            TeslaApiError::VehicleUnavailable() => "vehicle_unavailable",
+            TeslaApiError::WakeTimeout() => "wake_timeout",
+            TeslaApiError::UnknownApiError(_) => "api_error",
+            TeslaApiError::JsonDeserializationError(_) => "json",
+            // Only raised for ureq transport errors, e.g. timeouts and refused connections
+            TeslaApiError::Unknown => "transport",
+            TeslaApiError::Blocked(_) => "blocked",
+            TeslaApiError::CommandNotSupported { .. } => "command_not_supported",
+            TeslaApiError::CircuitOpen => "circuit_open",
+            TeslaApiError::RateLimited(_) => "rate_limited",
+        }
+    }
 }
 
 impl From<ErrorReply> for TeslaApiError {
@@ -31,10 +61,24 @@ impl From<ErrorReply> for TeslaApiError {
     }
 }
 
+/// Native coordinates closer than this (degrees) to the WGS-84 ones are considered the same.
+const NATIVE_COORDINATES_TOLERANCE: f64 = 0.001;
+
+/// Tokens are considered expired this long before they actually expire.
+const AUTH_TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+/// Share of the token lifetime after which it is refreshed ahead of time.
+const AUTH_TOKEN_REFRESH_FRACTION: f64 = 0.8;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AuthToken {
     pub access_token: String,
     pub refresh_token: String,
+    /// Lifetime of the access token in seconds, as returned by the token endpoint.
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    /// When the access token expires, `None` when unknown (e.g. tokens read from the environment).
+    #[serde(default)]
+    pub expires_at: Option<SystemTime>,
 }
 
 impl AuthToken {
@@ -42,6 +86,67 @@ impl AuthToken {
         AuthToken {
             access_token: env::var("TESLA_ACCESS_TOKEN").expect("TESLA_ACCESS_TOKEN environment variable is undefined"),
             refresh_token: env::var("TESLA_REFRESH_TOKEN").expect("TESLA_REFRESH_TOKEN environment variable is undefined"),
+            expires_in: None,
+            expires_at: None,
+        }
+    }
+
+    /// The tokens of every account to poll, in account order: `TESLA_ACCOUNTS`, a JSON array of
+    /// `{"access_token": "...", "refresh_token": "..."}`, or else the numbered `TESLA_ACCESS_TOKEN_1` /
+    /// `TESLA_REFRESH_TOKEN_1`, `TESLA_ACCESS_TOKEN_2` / ... pairs, or else the single account of
+    /// `TESLA_ACCESS_TOKEN` / `TESLA_REFRESH_TOKEN`.
+    pub fn accounts_from_env() -> anyhow::Result<Vec<AuthToken>> {
+        AuthToken::accounts_from_lookup(|name| env::var(name).ok())
+    }
+
+    fn accounts_from_lookup(lookup: impl Fn(&str) -> Option<String>) -> anyhow::Result<Vec<AuthToken>> {
+        let required = |name: String| lookup(&name).ok_or_else(|| anyhow!("{} environment variable is undefined", name));
+        let account = |access_token: String, refresh_token: String| AuthToken {
+            access_token,
+            refresh_token,
+            expires_in: None,
+            expires_at: None,
+        };
+
+        if let Some(accounts) = lookup("TESLA_ACCOUNTS") {
+            let accounts: Vec<AuthToken> = serde_json::from_str(&accounts).context("Invalid TESLA_ACCOUNTS")?;
+            if accounts.is_empty() {
+                return Err(anyhow!("TESLA_ACCOUNTS doesn't contain any account"));
+            }
+            return Ok(accounts);
+        }
+
+        let mut accounts = Vec::new();
+        while let Some(access_token) = lookup(&format!("TESLA_ACCESS_TOKEN_{}", accounts.len() + 1)) {
+            let refresh_token = required(format!("TESLA_REFRESH_TOKEN_{}", accounts.len() + 1))?;
+            accounts.push(account(access_token, refresh_token));
+        }
+        if accounts.is_empty() {
+            accounts.push(account(required("TESLA_ACCESS_TOKEN".to_string())?, required("TESLA_REFRESH_TOKEN".to_string())?));
+        }
+        Ok(accounts)
+    }
+
+    /// Derives `expires_at` from `expires_in`, relative to now.
+    pub fn with_expiry_from_now(mut self) -> Self {
+        self.expires_at = self.expires_in.map(|secs| SystemTime::now() + Duration::from_secs(secs));
+        self
+    }
+
+    /// True when the access token expires within the next 60 seconds.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| SystemTime::now() + AUTH_TOKEN_EXPIRY_MARGIN >= expires_at)
+    }
+
+    /// Time until 80% of the token lifetime has passed, zero when the expiry is unknown.
+    pub fn refresh_due_in(&self) -> Duration {
+        match (self.expires_in, self.expires_at) {
+            (Some(expires_in), Some(expires_at)) => {
+                let refresh_at = expires_at - Duration::from_secs(expires_in).mul_f64(1.0 - AUTH_TOKEN_REFRESH_FRACTION);
+                refresh_at.duration_since(SystemTime::now()).unwrap_or_default()
+            }
+            _ => Duration::from_secs(0),
         }
     }
 }
@@ -59,6 +164,14 @@ pub struct Vehicle {
     pub id: i64,
     pub display_name: String,
     pub state: String,
+    #[serde(default)]
+    pub calendar_enabled: bool,
+    /// Usually `null` in the owner-api, set in some fleet API contexts.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Empty if the API didn't send it.
+    #[serde(default)]
+    pub vin: String,
 
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
@@ -79,22 +192,181 @@ pub struct VehicleData {
     pub id: i64,
     pub display_name: String,
     pub state: String,
+    #[serde(default)]
+    pub vehicle_config: Option<VehicleConfig>,
     pub drive_state: VehicleDriveState,
     pub climate_state: VehicleClimateState,
     pub charge_state: VehicleChargeState,
     pub vehicle_state: VehicleState,
+    #[serde(default)]
+    pub gui_settings: VehicleGuiSettings,
 
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
 }
 
+impl VehicleData {
+    /// Fails with [`TeslaApiError::CommandNotSupported`] if the vehicle can't execute `command`.
+    /// Vehicles without a `vehicle_config` are assumed to support every command.
+    pub fn ensure_supports(&self, command: &CommandType) -> Result<(), TeslaApiError> {
+        match &self.vehicle_config {
+            Some(config) if !config.supports_command(command) => Err(TeslaApiError::CommandNotSupported {
+                command: command.name().to_string(),
+                vehicle: self.display_name.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// The power balance in kW: `charger_power` while charging, minus `drive_state.power` while
+    /// driving and zero otherwise. `drive_state.power` is reported in kW, positive while consuming.
+    pub fn net_power_kw(&self) -> f64 {
+        if self.charge_state.charging_state == ChargingState::Charging {
+            self.charge_state.charger_power
+        } else if self.drive_state.is_driving() {
+            -self.drive_state.power
+        } else {
+            0.0
+        }
+    }
+
+    /// Looks up a field the DTOs don't map, by a path such as `charge_state.some_new_field`.
+    ///
+    /// Paths starting with a state section are looked up in that section's `extra`, any other path
+    /// in the top-level `extra`.
+    pub fn extra_field(&self, path: &str) -> Option<&Value> {
+        match path.split_once('.') {
+            Some(("charge_state", field)) => self.charge_state.extra.get(field),
+            Some(("climate_state", field)) => self.climate_state.extra.get(field),
+            Some(("drive_state", field)) => self.drive_state.extra.get(field),
+            Some(("gui_settings", field)) => self.gui_settings.extra.get(field),
+            Some(("vehicle_state", field)) => self.vehicle_state.extra.get(field),
+            _ => self.extra.get(path),
+        }
+    }
+
+    /// Every numeric field the DTOs don't map, by dot-separated path, see [`traverse_extra`].
+    pub fn extra_values(&self) -> Vec<(String, f64)> {
+        let sections = [
+            ("", &self.extra),
+            ("charge_state", &self.charge_state.extra),
+            ("climate_state", &self.climate_state.extra),
+            ("drive_state", &self.drive_state.extra),
+            ("gui_settings", &self.gui_settings.extra),
+            ("vehicle_state", &self.vehicle_state.extra),
+        ];
+        sections
+            .iter()
+            .flat_map(|(prefix, extra)| {
+                let map: serde_json::Map<String, Value> = extra.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                traverse_extra(prefix, &map)
+            })
+            .collect()
+    }
+}
+
+/// Recursively collects the numeric leaves of `map` by dot-separated path, e.g.
+/// `charge_state.some_nested.value`. Numbers are collected as is, booleans as 0/1 and strings if
+/// they hold a number. Array elements are addressed by index.
+pub fn traverse_extra(prefix: &str, map: &serde_json::Map<String, Value>) -> Vec<(String, f64)> {
+    let mut values = Vec::new();
+    for (key, value) in map {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        collect_extra(path, value, &mut values);
+    }
+    values
+}
+
+fn collect_extra(path: String, value: &Value, values: &mut Vec<(String, f64)>) {
+    match value {
+        Value::Object(map) => values.extend(traverse_extra(&path, map)),
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_extra(format!("{}.{}", path, index), item, values);
+            }
+        }
+        Value::Number(number) => values.extend(number.as_f64().map(|number| (path, number))),
+        Value::Bool(flag) => values.push((path, if *flag { 1.0 } else { 0.0 })),
+        Value::String(text) => values.extend(text.trim().parse().ok().map(|number| (path, number))),
+        Value::Null => {}
+    }
+}
+
+/// Commands that depend on the vehicle's hardware, named after their owner-api endpoints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandType {
+    HonkHorn,
+    FlashLights,
+    ClimateOn,
+    ClimateOff,
+    SetChargeLimit,
+    ChargePortDoorOpen,
+    ActuateTrunk,
+    ShareNavigation,
+}
+
+impl CommandType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandType::HonkHorn => "honk_horn",
+            CommandType::FlashLights => "flash_lights",
+            CommandType::ClimateOn => "auto_conditioning_start",
+            CommandType::ClimateOff => "auto_conditioning_stop",
+            CommandType::SetChargeLimit => "set_charge_limit",
+            CommandType::ChargePortDoorOpen => "charge_port_door_open",
+            CommandType::ActuateTrunk => "actuate_trunk",
+            CommandType::ShareNavigation => "share",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VehicleConfig {
+    #[serde(default)]
+    pub can_accept_navigation_requests: bool,
+    #[serde(default)]
+    pub can_actuate_trunks: bool,
+    #[serde(default)]
+    pub motorized_charge_port: bool,
+    #[serde(default)]
+    pub car_type: String,
+
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl VehicleConfig {
+    pub fn supports_command(&self, command: &CommandType) -> bool {
+        match command {
+            CommandType::ActuateTrunk => self.can_actuate_trunks,
+            CommandType::ShareNavigation => self.can_accept_navigation_requests,
+            CommandType::ChargePortDoorOpen => self.motorized_charge_port,
+            CommandType::HonkHorn
+            | CommandType::FlashLights
+            | CommandType::ClimateOn
+            | CommandType::ClimateOff
+            | CommandType::SetChargeLimit => true,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VehicleDriveState {
     pub heading: f64,
     pub latitude: f64,
     pub longitude: f64,
+    /// Coordinate reference system of `latitude`/`longitude`, usually `wgs` (WGS-84).
+    #[serde(default)]
+    pub native_type: Option<String>,
+    /// Position in the `native_type` coordinate system, which differs from WGS-84 e.g. in China.
+    #[serde(default)]
+    pub native_latitude: Option<f64>,
+    #[serde(default)]
+    pub native_longitude: Option<f64>,
     pub power: f64,
-    pub shift_state: Option<String>,
+    /// `None` while the vehicle is off, values this version doesn't know are also read as `None`.
+    #[serde(default, deserialize_with = "deserialize_shift_state")]
+    pub shift_state: Option<ShiftState>,
     pub speed: Option<f64>,
     pub timestamp: i64,
 
@@ -103,16 +375,89 @@ pub struct VehicleDriveState {
 }
 
 impl VehicleDriveState {
+    /// Coordinate system as exported in the `native_type` label, `unknown` when absent.
+    pub fn native_type_label(&self) -> &str {
+        self.native_type.as_deref().unwrap_or("unknown")
+    }
+
+    /// The native coordinates when they are more than `NATIVE_COORDINATES_TOLERANCE` degrees off
+    /// `latitude`/`longitude`, `None` when they match or are missing.
+    pub fn native_coordinates_if_different(&self) -> Option<(f64, f64)> {
+        let (latitude, longitude) = (self.native_latitude?, self.native_longitude?);
+        if (latitude - self.latitude).abs() > NATIVE_COORDINATES_TOLERANCE
+            || (longitude - self.longitude).abs() > NATIVE_COORDINATES_TOLERANCE {
+            Some((latitude, longitude))
+        } else {
+            None
+        }
+    }
+
+    pub const SHIFT_STATE_LABELS: &'static [&'static str] = &["P", "D", "N", "R", "unknown"];
+
+    /// Shift state as exported in the `shift_state` label, `unknown` when absent or unrecognised.
+    pub fn shift_state_label(&self) -> &str {
+        self.shift_state.as_ref().map_or("unknown", ShiftState::as_str)
+    }
+
+    /// In gear or moving.
+    pub fn is_driving(&self) -> bool {
+        matches!(self.shift_state, Some(ShiftState::Reverse) | Some(ShiftState::Drive) | Some(ShiftState::Neutral))
+            || self.speed.unwrap_or_default() > 0.0
+    }
+
+    /// Shift state as a gauge value, see [`ShiftState::value`], `SHIFT_STATE_UNKNOWN_VALUE` when
+    /// absent or unrecognised so it doesn't read as Park.
     pub fn shift_state_value(&self) -> i64 {
-        match &self.shift_state.as_deref() {
-            Some("R") => -1,
-            Some("P") => 0,
-            Some("N") => 1,
-            Some("D") => 2,
-            Some(_) => 0,
-            None => 0,
+        self.shift_state.as_ref().map_or(SHIFT_STATE_UNKNOWN_VALUE, ShiftState::value)
+    }
+}
+
+/// Gauge value of an absent or unrecognised shift state.
+pub const SHIFT_STATE_UNKNOWN_VALUE: i64 = -2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ShiftState {
+    #[serde(rename = "P")]
+    Park,
+    #[serde(rename = "R")]
+    Reverse,
+    #[serde(rename = "N")]
+    Neutral,
+    #[serde(rename = "D")]
+    Drive,
+}
+
+impl ShiftState {
+    /// The value as sent by the API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShiftState::Park => "P",
+            ShiftState::Reverse => "R",
+            ShiftState::Neutral => "N",
+            ShiftState::Drive => "D",
         }
     }
+
+    /// Gauge value ordered by direction of travel: R=-1, P=0, N=1, D=2.
+    pub fn value(&self) -> i64 {
+        match self {
+            ShiftState::Reverse => -1,
+            ShiftState::Park => 0,
+            ShiftState::Neutral => 1,
+            ShiftState::Drive => 2,
+        }
+    }
+}
+
+fn deserialize_shift_state<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<ShiftState>, D::Error> {
+    let shift_state: Option<String> = Option::deserialize(deserializer)?;
+    Ok(match shift_state.as_deref() {
+        Some("P") => Some(ShiftState::Park),
+        Some("R") => Some(ShiftState::Reverse),
+        Some("N") => Some(ShiftState::Neutral),
+        Some("D") => Some(ShiftState::Drive),
+        _ => None,
+    })
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -127,18 +472,69 @@ pub struct VehicleClimateState {
     pub extra: HashMap<String, Value>,
 }
 
+/// The display preferences set in the vehicle. They don't change the units of the API, which always
+/// reports miles and Celsius, e.g. `gui_distance_units` is `"km/hr"` or `"mi/hr"`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct VehicleGuiSettings {
+    #[serde(default)]
+    pub gui_distance_units: String,
+    #[serde(default)]
+    pub gui_charge_rate_units: String,
+    /// `"Rated"` or `"Ideal"`.
+    #[serde(default)]
+    pub gui_range_display: String,
+    /// `"C"` or `"F"`.
+    #[serde(default)]
+    pub gui_temperature_units: String,
+    #[serde(default)]
+    pub gui_24_hour_time: bool,
+
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The API is loose with types in this section depending on firmware and charger: numbers come
+/// back as `null` while not charging, `charger_phases` is sometimes a string, and
+/// `fast_charger_type` is `""` instead of `null`.
+#[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VehicleChargeState {
     pub battery_level: i32,
     pub usable_battery_level: i32,
     pub battery_range: f64,
+    #[serde(default)]
+    pub charge_limit_soc: i32,
+    #[serde(default)]
+    pub charge_limit_soc_max: i32,
+    #[serde(default)]
+    pub charge_limit_soc_min: i32,
+    /// The default charge limit suggested by the vehicle.
+    #[serde(default)]
+    pub charge_limit_soc_std: i32,
+    /// Energy added during the current charging session (kWh), reset when a new session starts.
+    #[serde(default)]
+    pub charge_energy_added: f64,
+    /// Rated range added during the current charging session (mi).
+    #[serde(default)]
+    pub charge_miles_added_rated: f64,
+    #[serde_as(as = "DefaultOnNull")]
     pub charge_rate: f64,
+    #[serde_as(as = "DefaultOnNull")]
     pub charger_actual_current: f64,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    pub charger_phases: Option<i32>,
+    /// Charger power in kW, the API reports whole kW.
+    #[serde_as(as = "DefaultOnNull")]
     pub charger_power: f64,
+    #[serde_as(as = "DefaultOnNull")]
     pub charger_voltage: f64,
-    pub charging_state: String,
+    pub charging_state: ChargingState,
     pub est_battery_range: f64,
     pub fast_charger_present: bool,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub fast_charger_type: Option<String>,
     pub ideal_battery_range: f64,
     pub minutes_to_full_charge: i64,
     pub timestamp: i64,
@@ -147,15 +543,221 @@ pub struct VehicleChargeState {
     pub extra: HashMap<String, Value>,
 }
 
+/// `charging_state` of [`VehicleChargeState`], values this version doesn't know are kept as
+/// `Unknown` so new API values don't fail deserialization.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "String", into = "String")]
+pub enum ChargingState {
+    Disconnected,
+    Charging,
+    Stopped,
+    Complete,
+    NoPower,
+    Starting,
+    Unknown(String),
+}
+
+impl ChargingState {
+    /// The value as sent by the API.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ChargingState::Disconnected => "Disconnected",
+            ChargingState::Charging => "Charging",
+            ChargingState::Stopped => "Stopped",
+            ChargingState::Complete => "Complete",
+            ChargingState::NoPower => "NoPower",
+            ChargingState::Starting => "Starting",
+            ChargingState::Unknown(state) => state,
+        }
+    }
+}
+
+impl From<String> for ChargingState {
+    fn from(state: String) -> Self {
+        match state.as_str() {
+            "Disconnected" => ChargingState::Disconnected,
+            "Charging" => ChargingState::Charging,
+            "Stopped" => ChargingState::Stopped,
+            "Complete" => ChargingState::Complete,
+            "NoPower" => ChargingState::NoPower,
+            "Starting" => ChargingState::Starting,
+            _ => ChargingState::Unknown(state),
+        }
+    }
+}
+
+impl From<ChargingState> for String {
+    fn from(state: ChargingState) -> Self {
+        state.as_str().to_string()
+    }
+}
+
+impl fmt::Display for ChargingState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl VehicleChargeState {
+    pub const CHARGING_STATE_LABELS: &'static [&'static str] =
+        &["Disconnected", "Charging", "Complete", "Starting", "Stopped", "NoPower", "Unknown"];
+
+    /// Charging state as exported in the `state` label, `Unknown` for unrecognised values.
+    pub fn charging_state_label(&self) -> &str {
+        match &self.charging_state {
+            ChargingState::Unknown(_) => "Unknown",
+            state => state.as_str(),
+        }
+    }
+
+    /// Charger power in kW derived from voltage, current and phases, more precise than the whole
+    /// kW `charger_power`. A missing phase count is taken as single phase.
+    pub fn calculated_charger_power_kw(&self) -> f64 {
+        let phases = f64::from(self.charger_phases.unwrap_or(1));
+        self.charger_voltage * self.charger_actual_current * phases / 1000.0
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VehicleState {
+    /// Version of the vehicle's API, see [`api_version_supported_features`].
+    #[serde(default)]
+    pub api_version: Option<u32>,
     pub odometer: f64,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub sentry_mode: bool,
+    #[serde(default)]
+    pub is_user_present: bool,
+    #[serde(default)]
+    pub valet_mode: bool,
+    /// Only sent by some API versions.
+    #[serde(default)]
+    pub homelink_device_count: Option<i32>,
+    #[serde(default)]
+    pub homelink_nearby: Option<bool>,
+    #[serde(default)]
+    pub software_update: Option<SoftwareUpdate>,
+    /// Tire pressures (bar), `None` for vehicles without TPMS data.
+    #[serde(default)]
+    pub tpms_pressure_fl: Option<f64>,
+    #[serde(default)]
+    pub tpms_pressure_fr: Option<f64>,
+    #[serde(default)]
+    pub tpms_pressure_rl: Option<f64>,
+    #[serde(default)]
+    pub tpms_pressure_rr: Option<f64>,
     pub timestamp: i64,
 
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
 }
 
+impl VehicleState {
+    /// The features of the vehicle's API version, all of them when the version is unknown.
+    pub fn api_version_supported_features(&self) -> FeatureSet {
+        self.api_version.map_or(FeatureSet::ALL, api_version_supported_features)
+    }
+}
+
+/// Features that depend on the vehicle's `api_version`, so metrics of missing features can be
+/// skipped instead of being exported as 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeatureSet {
+    pub supports_sentry_mode: bool,
+    pub supports_homelink: bool,
+    pub supports_streaming: bool,
+}
+
+impl FeatureSet {
+    pub const ALL: FeatureSet = FeatureSet {
+        supports_sentry_mode: true,
+        supports_homelink: true,
+        supports_streaming: true,
+    };
+}
+
+/// The features supported by API version `v`: streaming from version 1, Sentry Mode from 4 and
+/// HomeLink from 7.
+pub fn api_version_supported_features(v: u32) -> FeatureSet {
+    FeatureSet {
+        supports_sentry_mode: v >= 4,
+        supports_homelink: v >= 7,
+        supports_streaming: v >= 1,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SoftwareUpdate {
+    /// Tesla's estimate of how long installing the pending update takes.
+    #[serde(default)]
+    pub expected_duration_sec: u32,
+    #[serde(default)]
+    pub install_perc: i32,
+    #[serde(default)]
+    pub status: String,
+
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// A past charging session from the undocumented `/api/1/vehicles/{id}/charges` endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChargeSession {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub energy_added: f64,
+    pub charge_limit: i32,
+    pub fast_charger: bool,
+
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// An entry of `/api/1/products`, either a vehicle or an energy site (Powerwall or solar).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Product {
+    /// Only set for energy sites.
+    #[serde(default)]
+    pub energy_site_id: Option<i64>,
+    #[serde(default)]
+    pub site_name: Option<String>,
+    /// `battery` or `solar` for energy sites.
+    #[serde(default)]
+    pub resource_type: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl Product {
+    /// The id and name of an energy site, `None` for vehicles.
+    pub fn energy_site(&self) -> Option<(i64, &str)> {
+        self.energy_site_id
+            .map(|id| (id, self.site_name.as_deref().unwrap_or_default()))
+    }
+}
+
+/// Reply of `/api/1/energy_sites/{id}/site_status`, powers in watts. Powers are positive while
+/// discharging the battery, producing solar and importing from the grid.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EnergySiteStatus {
+    #[serde(default)]
+    pub site_name: Option<String>,
+    #[serde(default)]
+    pub battery_power: f64,
+    #[serde(default)]
+    pub solar_power: f64,
+    #[serde(default)]
+    pub grid_power: f64,
+    #[serde(default)]
+    pub percentage_charged: f64,
+
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Reply<T> {
     pub response: T,
@@ -175,6 +777,76 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn should_expire_auth_token_within_margin() -> Result<()> {
+        let token: AuthToken = serde_json::from_str(r#"{"access_token": "a", "refresh_token": "r", "expires_in": 28800}"#)?;
+
+        assert!(!token.is_expired());
+        assert!(!token.clone().with_expiry_from_now().is_expired());
+        assert!(!AuthToken { expires_in: Some(90), ..token.clone() }.with_expiry_from_now().is_expired());
+        assert!(AuthToken { expires_in: Some(30), ..token }.with_expiry_from_now().is_expired());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_refresh_auth_token_after_most_of_its_lifetime() -> Result<()> {
+        let token: AuthToken = serde_json::from_str(r#"{"access_token": "a", "refresh_token": "r", "expires_in": 28800}"#)?;
+
+        assert_eq!(token.refresh_due_in(), Duration::from_secs(0));
+
+        let refresh_due_in = token.with_expiry_from_now().refresh_due_in();
+        assert!(refresh_due_in > Duration::from_secs(23030) && refresh_due_in <= Duration::from_secs(23040));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_accounts_from_env() -> Result<()> {
+        let lookup = |vars: &'static [(&'static str, &'static str)]| move |name: &str| {
+            vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        };
+        let tokens = |accounts: Vec<AuthToken>| -> Vec<String> {
+            accounts.into_iter().map(|account| account.access_token).collect()
+        };
+
+        let json = lookup(&[("TESLA_ACCOUNTS", r#"[{"access_token": "a1", "refresh_token": "r1"}, {"access_token": "a2", "refresh_token": "r2"}]"#)]);
+        assert_eq!(tokens(AuthToken::accounts_from_lookup(json)?), vec!["a1", "a2"]);
+
+        let numbered = lookup(&[("TESLA_ACCESS_TOKEN_1", "a1"), ("TESLA_REFRESH_TOKEN_1", "r1"), ("TESLA_ACCESS_TOKEN_2", "a2"),
+                                ("TESLA_REFRESH_TOKEN_2", "r2"), ("TESLA_ACCESS_TOKEN", "a")]);
+        assert_eq!(tokens(AuthToken::accounts_from_lookup(numbered)?), vec!["a1", "a2"]);
+
+        let single = lookup(&[("TESLA_ACCESS_TOKEN", "a"), ("TESLA_REFRESH_TOKEN", "r")]);
+        assert_eq!(tokens(AuthToken::accounts_from_lookup(single)?), vec!["a"]);
+
+        assert!(AuthToken::accounts_from_lookup(lookup(&[("TESLA_ACCESS_TOKEN_1", "a1")])).is_err());
+        assert!(AuthToken::accounts_from_lookup(lookup(&[("TESLA_ACCOUNTS", "[]")])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_commands_the_vehicle_does_not_support() -> Result<()> {
+        let mut vehicle_data: VehicleData = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+
+        assert_eq!(vehicle_data.ensure_supports(&CommandType::ActuateTrunk), Ok(()));
+
+        if let Some(config) = vehicle_data.vehicle_config.as_mut() {
+            config.can_actuate_trunks = false;
+        }
+        assert_eq!(
+            vehicle_data.ensure_supports(&CommandType::ActuateTrunk),
+            Err(TeslaApiError::CommandNotSupported {
+                command: "actuate_trunk".to_string(),
+                vehicle: "Bellwood Auto".to_string(),
+            })
+        );
+        assert_eq!(vehicle_data.ensure_supports(&CommandType::HonkHorn), Ok(()));
+
+        Ok(())
+    }
+
     #[test]
     fn should_deserialize_to_vehicle_data() -> Result<()> {
         let json = r#"
@@ -373,6 +1045,263 @@ mod tests {
         let vehicle_data: VehicleData = serde_json::from_str(json)?;
 
         assert_eq!(vehicle_data.id, 41614331478102467);
+        assert_eq!(vehicle_data.drive_state.native_type_label(), "wgs");
+        assert_eq!(vehicle_data.extra_field("charge_state.charge_current_request"), Some(&Value::from(32)));
+        assert_eq!(vehicle_data.extra_field("api_version"), Some(&Value::from(14)));
+        assert_eq!(vehicle_data.extra_field("charge_state.battery_level"), None);
+        assert!(vehicle_data.extra_values().contains(&("charge_state.charge_current_request".to_string(), 32.0)));
+        assert_eq!(vehicle_data.vehicle_state.api_version, Some(14));
+        assert_eq!(vehicle_data.gui_settings.gui_distance_units, "mi/hr");
+        assert_eq!(vehicle_data.gui_settings.gui_temperature_units, "F");
+        assert!(!vehicle_data.gui_settings.gui_24_hour_time);
+        assert_eq!(vehicle_data.extra_field("gui_settings.show_range_units"), Some(&Value::from(false)));
+        assert!(!vehicle_data.extra.contains_key("gui_settings"));
+        assert!(vehicle_data.vehicle_state.locked);
+        assert!(!vehicle_data.vehicle_state.is_user_present);
+        assert!(!vehicle_data.vehicle_state.valet_mode);
+        assert_eq!(vehicle_data.extra_field("vehicle_state.locked"), None);
+        assert_eq!(vehicle_data.vehicle_state.software_update.map(|update| update.expected_duration_sec), Some(2700));
+        assert_eq!(vehicle_data.vehicle_state.homelink_nearby, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_support_features_by_api_version() {
+        assert_eq!(api_version_supported_features(3), FeatureSet {
+            supports_sentry_mode: false,
+            supports_homelink: false,
+            supports_streaming: true,
+        });
+        assert!(api_version_supported_features(4).supports_sentry_mode);
+        assert!(!api_version_supported_features(6).supports_homelink);
+        assert_eq!(api_version_supported_features(14), FeatureSet::ALL);
+    }
+
+    #[test]
+    fn should_parse_energy_site_products() -> Result<()> {
+        let json = r#"
+        [
+          { "id": 41614331478102467, "vehicle_id": 1350453399, "display_name": "Bellwood Auto" },
+          { "energy_site_id": 2252187704244195, "resource_type": "battery", "site_name": "Home" }
+        ]
+        "#;
+
+        let products: Vec<Product> = serde_json::from_str(json)?;
+        let energy_sites: Vec<(i64, &str)> = products.iter().filter_map(Product::energy_site).collect();
+
+        assert_eq!(energy_sites, vec![(2252187704244195, "Home")]);
+
+        let status: EnergySiteStatus =
+            serde_json::from_str(r#"{ "site_name": "Home", "battery_power": -1250.5, "percentage_charged": 87.3 }"#)?;
+        assert_eq!(status.battery_power, -1250.5);
+        assert_eq!(status.solar_power, 0.0);
+        assert_eq!(status.percentage_charged, 87.3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_only_report_native_coordinates_that_differ() -> Result<()> {
+        let vehicle_data: VehicleData = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        let mut drive_state = vehicle_data.drive_state;
+        assert_eq!(drive_state.native_coordinates_if_different(), None);
+
+        drive_state.native_latitude = Some(drive_state.latitude + 0.002);
+        assert_eq!(drive_state.native_coordinates_if_different(), Some((drive_state.latitude + 0.002, drive_state.longitude)));
+
+        drive_state.native_longitude = None;
+        assert_eq!(drive_state.native_coordinates_if_different(), None);
+
+        Ok(())
+    }
+
+    fn charge_state_with(field: &str, value: Value) -> Result<VehicleChargeState> {
+        let mut vehicle_data: Value = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        vehicle_data["charge_state"][field] = value;
+        Ok(serde_json::from_value(vehicle_data["charge_state"].clone())?)
+    }
+
+    #[test]
+    fn should_parse_optional_tire_pressures() -> Result<()> {
+        let mut vehicle_data: Value = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        let vehicle_state: VehicleState = serde_json::from_value(vehicle_data["vehicle_state"].clone())?;
+        assert_eq!(vehicle_state.tpms_pressure_fl, None);
+
+        vehicle_data["vehicle_state"]["tpms_pressure_fl"] = serde_json::json!(2.9);
+        vehicle_data["vehicle_state"]["tpms_pressure_rr"] = Value::Null;
+        let vehicle_state: VehicleState = serde_json::from_value(vehicle_data["vehicle_state"].clone())?;
+        assert_eq!(vehicle_state.tpms_pressure_fl, Some(2.9));
+        assert_eq!(vehicle_state.tpms_pressure_rr, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_parse_charge_limits() -> Result<()> {
+        let charge_state = charge_state_with("charge_limit_soc", serde_json::json!(80))?;
+
+        assert_eq!(charge_state.charge_limit_soc, 80);
+        assert_eq!(charge_state.charge_limit_soc_max, 100);
+        assert_eq!(charge_state.charge_limit_soc_min, 50);
+        assert_eq!(charge_state.charge_limit_soc_std, 90);
+        assert_eq!(charge_state.charge_miles_added_rated, 137.5);
+        assert!(!charge_state.extra.contains_key("charge_limit_soc_std"));
+
+        let mut vehicle_data: Value = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        let fields = vehicle_data["charge_state"].as_object_mut().expect("Charge state isn't an object");
+        fields.retain(|field, _| !field.starts_with("charge_limit_soc"));
+        let charge_state: VehicleChargeState = serde_json::from_value(vehicle_data["charge_state"].clone())?;
+        assert_eq!(charge_state.charge_limit_soc, 0);
+        assert_eq!(charge_state.charge_limit_soc_max, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_parse_partial_software_update() -> Result<()> {
+        let software_update: SoftwareUpdate = serde_json::from_str(r#"{"version": " "}"#)?;
+
+        assert_eq!(software_update.expected_duration_sec, 0);
+        assert_eq!(software_update.install_perc, 0);
+        assert_eq!(software_update.status, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_map_unknown_shift_states_to_sentinel() -> Result<()> {
+        let drive_state_with = |shift_state: Value| -> Result<VehicleDriveState> {
+            let mut vehicle_data: Value = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+            vehicle_data["drive_state"]["shift_state"] = shift_state;
+            Ok(serde_json::from_value(vehicle_data["drive_state"].clone())?)
+        };
+
+        let reverse = drive_state_with(serde_json::json!("R"))?;
+        assert_eq!((reverse.shift_state, reverse.shift_state_value(), reverse.shift_state_label()),
+                   (Some(ShiftState::Reverse), -1, "R"));
+
+        let off = drive_state_with(Value::Null)?;
+        assert_eq!((off.shift_state, off.shift_state_value(), off.shift_state_label()),
+                   (None, SHIFT_STATE_UNKNOWN_VALUE, "unknown"));
+
+        assert_eq!(drive_state_with(serde_json::json!("X"))?.shift_state, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_unknown_charging_states() -> Result<()> {
+        let charging = charge_state_with("charging_state", serde_json::json!("Charging"))?;
+        assert_eq!(charging.charging_state, ChargingState::Charging);
+        assert_eq!(charging.charging_state_label(), "Charging");
+
+        let unknown = charge_state_with("charging_state", serde_json::json!("Preconditioning"))?;
+        assert_eq!(unknown.charging_state, ChargingState::Unknown("Preconditioning".to_string()));
+        assert_eq!(unknown.charging_state_label(), "Unknown");
+        assert_eq!(serde_json::to_value(&unknown.charging_state)?, serde_json::json!("Preconditioning"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_default_null_charger_numbers_to_zero() -> Result<()> {
+        assert_eq!(charge_state_with("charger_power", Value::Null)?.charger_power, 0.0);
+        assert_eq!(charge_state_with("charger_voltage", Value::Null)?.charger_voltage, 0.0);
+        assert_eq!(charge_state_with("charger_actual_current", Value::Null)?.charger_actual_current, 0.0);
+        assert_eq!(charge_state_with("charge_rate", Value::Null)?.charge_rate, 0.0);
+        assert_eq!(charge_state_with("charger_power", serde_json::json!(11))?.charger_power, 11.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_accept_charger_phases_as_number_or_string() -> Result<()> {
+        assert_eq!(charge_state_with("charger_phases", Value::Null)?.charger_phases, None);
+        assert_eq!(charge_state_with("charger_phases", serde_json::json!(3))?.charger_phases, Some(3));
+        assert_eq!(charge_state_with("charger_phases", serde_json::json!("1"))?.charger_phases, Some(1));
+        assert!(charge_state_with("charger_phases", serde_json::json!("three")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_treat_empty_fast_charger_type_as_none() -> Result<()> {
+        assert_eq!(charge_state_with("fast_charger_type", serde_json::json!(""))?.fast_charger_type, None);
+        assert_eq!(
+            charge_state_with("fast_charger_type", serde_json::json!("Supercharger"))?.fast_charger_type,
+            Some("Supercharger".to_string())
+        );
+
+        let mut vehicle_data: Value = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        if let Some(charge_state) = vehicle_data["charge_state"].as_object_mut() {
+            charge_state.remove("fast_charger_type");
+            charge_state.remove("charger_phases");
+        }
+        let charge_state: VehicleChargeState = serde_json::from_value(vehicle_data["charge_state"].clone())?;
+        assert_eq!(charge_state.fast_charger_type, None);
+        assert_eq!(charge_state.charger_phases, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_calculate_charger_power_from_voltage_current_and_phases() -> Result<()> {
+        let mut charge_state = charge_state_with("charger_phases", serde_json::json!(3))?;
+        charge_state.charger_voltage = 230.0;
+        charge_state.charger_actual_current = 16.0;
+
+        assert_eq!(charge_state.calculated_charger_power_kw(), 11.04);
+        assert_eq!(VehicleChargeState { charger_phases: None, ..charge_state }.calculated_charger_power_kw(), 3.68);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_deserialize_vehicle_color_and_vin() -> Result<()> {
+        let vehicle: Vehicle = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        assert_eq!(vehicle.color, None);
+        assert_eq!(vehicle.vin, "5YJ3E1EA4KF311487");
+
+        let vehicle: Vehicle = serde_json::from_str(r#"{"id": 1, "display_name": "Car", "state": "online", "color": "Red"}"#)?;
+        assert_eq!(vehicle.color, Some("Red".to_string()));
+        assert_eq!(vehicle.vin, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_compute_net_power_by_activity() -> Result<()> {
+        let mut vehicle_data: VehicleData = serde_json::from_str(include_str!("../../tests/fixtures/vehicle_data.json"))?;
+        vehicle_data.charge_state.charger_power = 7.0;
+        vehicle_data.drive_state.power = 12.0;
+        assert_eq!(vehicle_data.net_power_kw(), 0.0);
+
+        vehicle_data.drive_state.shift_state = Some(ShiftState::Drive);
+        assert_eq!(vehicle_data.net_power_kw(), -12.0);
+
+        vehicle_data.drive_state.shift_state = None;
+        vehicle_data.charge_state.charging_state = ChargingState::Charging;
+        assert_eq!(vehicle_data.net_power_kw(), 7.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_traverse_nested_extra_fields() -> Result<()> {
+        let map: serde_json::Map<String, Value> = serde_json::from_str(r#"{
+            "some_nested": {"value": 1.5, "enabled": true, "name": "x", "missing": null},
+            "levels": [10, "20"]
+        }"#)?;
+
+        let mut values = traverse_extra("charge_state", &map);
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(values, vec![
+            ("charge_state.levels.0".to_string(), 10.0),
+            ("charge_state.levels.1".to_string(), 20.0),
+            ("charge_state.some_nested.enabled".to_string(), 1.0),
+            ("charge_state.some_nested.value".to_string(), 1.5),
+        ]);
 
         Ok(())
     }
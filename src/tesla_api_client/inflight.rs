@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Condvar, Mutex};
+
+use anyhow::Result;
+
+/// Deduplicates concurrent requests for the same key, e.g. the poller and an HTTP handler both
+/// fetching a vehicle's data: the first caller makes the request and later callers wait for its
+/// result instead of making their own.
+///
+/// Errors can't be shared, so waiters make their own request when the first one failed.
+#[derive(Debug)]
+pub struct InflightRequestCache<K, V> {
+    inflight: Mutex<HashMap<K, Arc<Inflight<V>>>>,
+}
+
+#[derive(Debug)]
+struct Inflight<V> {
+    /// `None` while the request is running, then `Some(None)` if it failed.
+    result: Mutex<Option<Option<V>>>,
+    done: Condvar,
+}
+
+/// Completes the request on drop, so waiters are released even if `fetch` panics.
+struct Completion<'a, K: Eq + Hash, V> {
+    cache: &'a InflightRequestCache<K, V>,
+    key: &'a K,
+    inflight: Arc<Inflight<V>>,
+    value: Option<V>,
+}
+
+impl<'a, K: Eq + Hash, V> Drop for Completion<'a, K, V> {
+    fn drop(&mut self) {
+        self.cache.inflight.lock().expect("Inflight requests lock is poisoned").remove(self.key);
+        *self.inflight.result.lock().expect("Inflight request lock is poisoned") = Some(self.value.take());
+        self.inflight.done.notify_all();
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> InflightRequestCache<K, V> {
+    pub fn new() -> Self {
+        InflightRequestCache { inflight: Mutex::new(HashMap::new()) }
+    }
+
+    /// Calls `fetch` unless a request for `key` is already running, in which case its result is
+    /// returned once it completes.
+    pub fn get_or_fetch(&self, key: &K, fetch: impl FnOnce() -> Result<V>) -> Result<V> {
+        let (inflight, is_first) = {
+            let mut requests = self.inflight.lock().expect("Inflight requests lock is poisoned");
+            match requests.get(key) {
+                Some(inflight) => (inflight.clone(), false),
+                None => {
+                    let inflight = Arc::new(Inflight { result: Mutex::new(None), done: Condvar::new() });
+                    requests.insert(key.clone(), inflight.clone());
+                    (inflight, true)
+                }
+            }
+        };
+
+        if !is_first {
+            let mut result = inflight.result.lock().expect("Inflight request lock is poisoned");
+            while result.is_none() {
+                result = inflight.done.wait(result).expect("Inflight request lock is poisoned");
+            }
+            return match result.clone().flatten() {
+                Some(value) => Ok(value),
+                None => fetch(),
+            };
+        }
+
+        let mut completion = Completion { cache: self, key, inflight, value: None };
+        let result = fetch();
+        completion.value = result.as_ref().ok().cloned();
+        result
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Default for InflightRequestCache<K, V> {
+    fn default() -> Self {
+        InflightRequestCache::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn should_share_result_of_concurrent_request() {
+        let cache = Arc::new(InflightRequestCache::<i64, String>::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let fetches: Vec<_> = (0..2)
+            .map(|i| {
+                let cache = cache.clone();
+                let calls = calls.clone();
+                sleep(Duration::from_millis(50 * i));
+                thread::spawn(move || {
+                    cache.get_or_fetch(&1, || {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        sleep(Duration::from_millis(200));
+                        Ok("data".to_string())
+                    })
+                })
+            })
+            .collect();
+
+        for fetch in fetches {
+            assert_eq!(fetch.join().unwrap().unwrap(), "data");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(cache.get_or_fetch(&1, || Ok("fresh".to_string())).unwrap(), "fresh");
+        assert!(cache.get_or_fetch(&1, || Err(anyhow!("failed"))).is_err());
+    }
+}
@@ -1,26 +1,196 @@
+//! Blocking client for the Tesla Owner API.
+//!
+//! Every request returns an [`anyhow::Result`]; API failures are reported as a
+//! [`TeslaApiError`] which can be recovered with `downcast_ref`:
+//!
+//! ```no_run
+//! use tesla_api_exporter::tesla_api_client::dtos::{AuthToken, TeslaApiError};
+//! use tesla_api_exporter::tesla_api_client::TeslaApiClient;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let client = TeslaApiClient::create(AuthToken::from_env())?;
+//! for vehicle in client.fetch_vehicles()? {
+//!     match client.fetch_vehicle_data(&vehicle.id) {
+//!         Ok(data) => println!("{} is at {}%", vehicle.display_name, data.charge_state.battery_level),
+//!         Err(e) if e.downcast_ref::<TeslaApiError>() == Some(&TeslaApiError::VehicleUnavailable()) => {
+//!             println!("{} is asleep", vehicle.display_name)
+//!         }
+//!         Err(e) => return Err(e),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
 use std::env;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use log::warn;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use rocket_prometheus::prometheus::{HistogramOpts, HistogramVec};
 use serde::de::DeserializeOwned;
 use ureq::{Agent, Error, Error::Status, Request, Response};
 
+use crate::metrics::metric_opts;
+use crate::tesla_api_client::inflight::InflightRequestCache;
+use crate::tesla_api_client::retry::RetryPolicy;
+use crate::tesla_api_client::token_store::{FileTokenStore, TokenStore};
 use crate::tesla_api_client::dtos::{
-    AuthToken, ErrorReply, Reply, TeslaApiError, Vehicle, VehicleData,
+    AuthToken, ChargeSession, EnergySiteStatus, ErrorReply, Product, Reply, TeslaApiError, Vehicle,
+    VehicleData,
 };
 
+#[cfg(feature = "async")]
+pub mod async_client;
+pub mod circuit_breaker;
 pub mod dtos;
+pub mod inflight;
+pub mod retry;
+pub mod tls;
+pub mod token_refresh_scheduler;
+pub mod token_store;
+pub mod token_watcher;
 
-static API_URL: &str = "https://owner-api.teslamotors.com";
-static AUTH_API_URL: &str = "https://auth.tesla.com";
+static DEFAULT_API_URL: &str = "https://owner-api.teslamotors.com";
+static DEFAULT_AUTH_API_URL: &str = "https://auth.tesla.com";
 static USER_AGENT: &str = "tesla-api-exporter";
 
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to back off after a 429 response without a usable `Retry-After` header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Observed for every Owner API request, registered by the poller.
+pub static API_CALL_DURATION_HISTOGRAM: Lazy<HistogramVec> = Lazy::new(|| {
+    let opts = metric_opts("tesla_api_call_duration_seconds", "Time until the Tesla API responded to a request");
+    HistogramVec::new(HistogramOpts::from(opts).buckets(vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]), &["endpoint", "status"])
+        .expect("Could not create lazy HistogramVec")
+});
+
+/// The outcome of sending a request. The `ureq::Error` is boxed, a status error holds the whole
+/// response.
+pub type CallResult = Result<Response, Box<Error>>;
+
+/// A vehicle with the error that prevented fetching its data, the vehicle boxed to keep the
+/// `Err` variant of [`VehicleDataResult`] small.
+pub type FailedVehicle = (Box<Vehicle>, anyhow::Error);
+
+pub type VehicleDataResult = Result<VehicleData, FailedVehicle>;
+
+/// How long waking a vehicle took.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WakeResult {
+    /// `wake_up` requests sent, including the first.
+    pub attempts: u32,
+    pub total_duration: Duration,
+}
+
 #[derive(Debug, Clone)]
 pub struct TeslaApiClient {
     agent: Agent,
+    /// Owner API base URL without a trailing `/`.
+    api_url: String,
+    /// Auth API base URL without a trailing `/`.
+    auth_api_url: String,
+    /// Shared by every clone of the client, so a refreshed or reloaded token is used everywhere.
+    auth_token: Arc<RwLock<AuthToken>>,
+    /// Shared by every clone, so concurrent `fetch_vehicle_data` calls for a vehicle make one request.
+    vehicle_data_requests: Arc<InflightRequestCache<i64, VehicleData>>,
+    /// Receives every refreshed token when configured.
+    token_store: Option<Arc<dyn TokenStore>>,
+    /// Retries transient failures when configured, otherwise every request is sent once.
+    retry_policy: Option<RetryPolicy>,
+    /// Held while refreshing an expiring token, so concurrent requests refresh it once.
+    refresh_lock: Arc<Mutex<()>>,
+}
+
+/// Creates a [`TeslaApiClient`], see [`TeslaApiClient::builder`].
+#[derive(Debug, Clone)]
+pub struct TeslaApiClientBuilder {
     auth_token: AuthToken,
+    api_url: String,
+    auth_api_url: String,
+    token_store: Option<Arc<dyn TokenStore>>,
+    retry_policy: Option<RetryPolicy>,
+    read_timeout: Duration,
+    write_timeout: Duration,
+}
+
+impl TeslaApiClientBuilder {
+    /// The Owner API base URL, `TESLA_API_URL` or `https://owner-api.teslamotors.com` by default.
+    pub fn api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    /// The auth API base URL, `TESLA_AUTH_URL` or `https://auth.tesla.com` by default.
+    pub fn auth_api_url(mut self, auth_api_url: impl Into<String>) -> Self {
+        self.auth_api_url = auth_api_url.into();
+        self
+    }
+
+    /// Persists refreshed tokens to `token_store`, a [`FileTokenStore`] for `TESLA_TOKEN_FILE` by
+    /// default. A token already in the store is used instead of the one passed to the builder.
+    pub fn token_store(mut self, token_store: Arc<dyn TokenStore>) -> Self {
+        self.token_store = Some(token_store);
+        self
+    }
+
+    /// Retries timeouts, rate limiting and unavailable vehicles or services with backoff, by default
+    /// requests aren't retried.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// How long to wait for a response, 5 seconds by default.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// How long to wait while sending a request, 5 seconds by default.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// See [`TeslaApiClient::create`].
+    pub fn build(self) -> Result<TeslaApiClient> {
+        let mut builder = ureq::AgentBuilder::new()
+            .timeout_read(self.read_timeout)
+            .timeout_write(self.write_timeout);
+
+        if let Ok(pin) = env::var("TESLA_CERT_PIN_SHA256") {
+            builder = builder.tls_config(tls::pinned_tls_config(&pin).context("Invalid TESLA_CERT_PIN_SHA256")?);
+        }
+
+        let agent: Agent = builder.build();
+
+        let auth_token = match &self.token_store {
+            None => self.auth_token,
+            Some(token_store) => match token_store.load() {
+                Ok(auth_token) => auth_token,
+                Err(err) => {
+                    info!("Using the configured auth token, no stored token: {:#}", err);
+                    self.auth_token
+                }
+            },
+        };
+
+        Ok(TeslaApiClient {
+            agent,
+            api_url: self.api_url.trim_end_matches('/').to_string(),
+            auth_api_url: self.auth_api_url.trim_end_matches('/').to_string(),
+            auth_token: Arc::new(RwLock::new(auth_token)),
+            vehicle_data_requests: Arc::new(InflightRequestCache::new()),
+            token_store: self.token_store,
+            retry_policy: self.retry_policy,
+            refresh_lock: Arc::new(Mutex::new(())),
+        })
+    }
 }
 
 pub struct Auth {
@@ -38,150 +208,609 @@ impl Auth {
 }
 
 
+/// The Tesla API calls of the poller, implemented by [`TeslaApiClient`], by the
+/// [`CircuitBreaker`](circuit_breaker::CircuitBreaker) wrapping it and by
+/// `testing::FakeTeslaApiClient` in tests.
+pub trait TeslaApiClientTrait {
+    fn fetch_vehicle(&self, vehicle_id: &i64) -> Result<Vehicle>;
+    fn fetch_vehicles(&self) -> Result<Vec<Vehicle>>;
+    fn fetch_vehicle_data(&self, vehicle_id: &i64) -> Result<VehicleData>;
+    fn fetch_all_vehicles_data(&self) -> Result<Vec<VehicleDataResult>>;
+    fn wake_vehicle(&self, vehicle_id: &i64) -> Result<Vehicle>;
+    fn wake_vehicle_poll_observed(&self, vehicle_id: &i64, on_attempt: &mut dyn FnMut()) -> Result<WakeResult>;
+    fn refresh_auth(&self) -> Result<()>;
+
+    fn wake_vehicle_poll(&self, vehicle_id: &i64) -> Result<WakeResult> {
+        self.wake_vehicle_poll_observed(vehicle_id, &mut || {})
+    }
+}
+
+impl TeslaApiClientTrait for TeslaApiClient {
+    fn fetch_vehicle(&self, vehicle_id: &i64) -> Result<Vehicle> {
+        TeslaApiClient::fetch_vehicle(self, vehicle_id)
+    }
+
+    fn fetch_vehicles(&self) -> Result<Vec<Vehicle>> {
+        TeslaApiClient::fetch_vehicles(self)
+    }
+
+    fn fetch_vehicle_data(&self, vehicle_id: &i64) -> Result<VehicleData> {
+        TeslaApiClient::fetch_vehicle_data(self, vehicle_id)
+    }
+
+    fn fetch_all_vehicles_data(&self) -> Result<Vec<VehicleDataResult>> {
+        TeslaApiClient::fetch_all_vehicles_data(self)
+    }
+
+    fn wake_vehicle(&self, vehicle_id: &i64) -> Result<Vehicle> {
+        TeslaApiClient::wake_vehicle(self, vehicle_id)
+    }
+
+    fn wake_vehicle_poll_observed(&self, vehicle_id: &i64, on_attempt: &mut dyn FnMut()) -> Result<WakeResult> {
+        TeslaApiClient::wake_vehicle_poll_observed(self, vehicle_id, on_attempt)
+    }
+
+    fn refresh_auth(&self) -> Result<()> {
+        TeslaApiClient::refresh_auth(self)
+    }
+
+    fn wake_vehicle_poll(&self, vehicle_id: &i64) -> Result<WakeResult> {
+        TeslaApiClient::wake_vehicle_poll(self, vehicle_id)
+    }
+}
+
 impl TeslaApiClient {
+    /// Creates a client authenticating with `auth_token`.
+    ///
+    /// No request is made, so an invalid token only surfaces as [`TeslaApiError::LoginFailure`]
+    /// on the first call. When `TESLA_CERT_PIN_SHA256` is set the server certificate is pinned
+    /// to that hash, and an error is returned if it is not a valid SHA-256 hex digest.
+    ///
+    /// ```no_run
+    /// use tesla_api_exporter::tesla_api_client::dtos::AuthToken;
+    /// use tesla_api_exporter::tesla_api_client::TeslaApiClient;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let client = TeslaApiClient::create(AuthToken::from_env())?;
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn create(auth_token: AuthToken) -> Result<TeslaApiClient> {
-        let agent: Agent = ureq::AgentBuilder::new()
-            .timeout_read(Duration::from_secs(5))
-            .timeout_write(Duration::from_secs(5))
-            .build();
+        TeslaApiClient::builder(auth_token).build()
+    }
 
-        Ok(TeslaApiClient { agent, auth_token })
+    /// A builder for a client with custom timeouts or base URLs, e.g. for regional hosts or a
+    /// mock server.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use tesla_api_exporter::tesla_api_client::dtos::AuthToken;
+    /// use tesla_api_exporter::tesla_api_client::TeslaApiClient;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let client = TeslaApiClient::builder(AuthToken::from_env())
+    ///     .read_timeout(Duration::from_secs(30))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder(auth_token: AuthToken) -> TeslaApiClientBuilder {
+        TeslaApiClientBuilder {
+            auth_token,
+            api_url: env::var("TESLA_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string()),
+            auth_api_url: env::var("TESLA_AUTH_URL").unwrap_or_else(|_| DEFAULT_AUTH_API_URL.to_string()),
+            token_store: FileTokenStore::from_env().map(|store| Arc::new(store) as Arc<dyn TokenStore>),
+            retry_policy: None,
+            read_timeout: DEFAULT_TIMEOUT,
+            write_timeout: DEFAULT_TIMEOUT,
+        }
     }
 
-    pub fn refresh_auth(&mut self) -> anyhow::Result<()> {
+    /// Exchanges the refresh token for a new access token and replaces the current one.
+    ///
+    /// The new token is saved to the token store when one is configured, failing to save it is
+    /// only logged. Returns [`TeslaApiError::LoginFailure`] if the refresh token was rejected.
+    pub fn refresh_auth(&self) -> anyhow::Result<()> {
+        let refresh_token = self.read_auth_token().refresh_token.clone();
         let api_url = &format!(
             "{api_url}/oauth2/v3/token",
-            api_url = AUTH_API_URL
+            api_url = self.auth_api_url
         );
-        let result = self.http_post(api_url)
+        let result = self.agent.post(api_url)
+            .set("User-Agent", USER_AGENT)
             .send_json(ureq::json!({
                 "grant_type": "refresh_token",
                 "client_id": "ownerapi",
                 "scope": "openid email offline_access",
-                "refresh_token": &self.auth_token.refresh_token,
-            }));
+                "refresh_token": &refresh_token,
+            }))
+            .map_err(Box::new);
 
-        self.auth_token = TeslaApiClient::handle_result::<AuthToken>(result)?;
+        let auth_token = TeslaApiClient::handle_result::<AuthToken>(result)
+            .context("Failed to refresh auth token")?
+            .with_expiry_from_now();
+        if let Some(token_store) = &self.token_store {
+            if let Err(err) = token_store.save(&auth_token) {
+                warn!("Failed to save refreshed auth token: {:#}", err);
+            }
+        }
+        *self.auth_token.write().expect("Auth token lock is poisoned") = auth_token;
         Ok(())
     }
 
+    /// Whether the access token is expired or about to expire, `false` when the expiry is unknown.
+    pub fn is_auth_expired(&self) -> bool {
+        self.read_auth_token().is_expired()
+    }
+
+    /// The auth token shared by this client and its clones, e.g. to replace it from another thread.
+    pub fn auth_token(&self) -> Arc<RwLock<AuthToken>> {
+        self.auth_token.clone()
+    }
+
+    /// The store refreshed tokens are saved to, if any.
+    pub fn token_store(&self) -> Option<Arc<dyn TokenStore>> {
+        self.token_store.clone()
+    }
+
+    fn read_auth_token(&self) -> RwLockReadGuard<'_, AuthToken> {
+        self.auth_token.read().expect("Auth token lock is poisoned")
+    }
+
+    /// Fetches a single vehicle without waking it.
+    ///
+    /// A rejected access token is refreshed and the request retried once; returns
+    /// [`TeslaApiError::LoginFailure`] if it is rejected again and
+    /// [`TeslaApiError::UnknownApiError`] for other API errors, e.g. an unknown `vehicle_id`.
     pub fn fetch_vehicle(&self, vehicle_id: &i64) -> anyhow::Result<Vehicle> {
         let api_url = format!("{api_url}/api/1/vehicles/{id}",
-                              api_url = API_URL,
+                              api_url = self.api_url,
                               id = vehicle_id,
         );
-        let result = self
-            .http_get(&api_url)
-            .call();
+        let result = self.call_with_refresh(|| call_timed("fetch_vehicle", self.http_get(&api_url)));
 
-        let reply = TeslaApiClient::handle_result::<Reply<Vehicle>>(result)?;
+        let reply = TeslaApiClient::handle_result::<Reply<Vehicle>>(result)
+            .with_context(|| format!("Failed to fetch vehicle {}", vehicle_id))?;
         Ok(reply.response)
     }
 
+    /// Fetches all vehicles of the account without waking them.
+    ///
+    /// Returns [`TeslaApiError::LoginFailure`] if the access token was rejected even after
+    /// refreshing it and [`TeslaApiError::Blocked`] if Tesla blocked the request.
     pub fn fetch_vehicles(&self) -> anyhow::Result<Vec<Vehicle>> {
-        let api_url = format!("{api_url}/api/1/vehicles", api_url = API_URL);
-        let result = self
-            .http_get(&api_url)
-            .call();
+        let api_url = format!("{api_url}/api/1/vehicles", api_url = self.api_url);
+        let result = self.call_with_refresh(|| call_timed("fetch_vehicles", self.http_get(&api_url)));
 
-        let reply = TeslaApiClient::handle_result::<Reply<Vec<Vehicle>>>(result)?;
+        let reply = TeslaApiClient::handle_result::<Reply<Vec<Vehicle>>>(result)
+            .context("Failed to fetch vehicles")?;
         Ok(reply.response)
     }
 
+    /// Fetches the charge, climate, drive and vehicle state of a vehicle.
+    ///
+    /// Returns [`TeslaApiError::VehicleUnavailable`] if the vehicle is asleep or offline, see
+    /// [`TeslaApiClient::wake_vehicle_poll`], and [`TeslaApiError::JsonDeserializationError`] if
+    /// the reply doesn't match [`VehicleData`].
+    ///
+    /// Concurrent calls for the same vehicle, from any clone of the client, share one request.
     pub fn fetch_vehicle_data(&self, vehicle_id: &i64) -> anyhow::Result<VehicleData> {
+        self.vehicle_data_requests.get_or_fetch(vehicle_id, || self.request_vehicle_data(vehicle_id))
+    }
+
+    fn request_vehicle_data(&self, vehicle_id: &i64) -> anyhow::Result<VehicleData> {
         let api_url = format!(
             "{api_url}/api/1/vehicles/{id}/vehicle_data",
-            api_url = API_URL,
+            api_url = self.api_url,
             id = vehicle_id
         );
 
-        let result = self.http_get(&api_url).call();
+        let result = self.call_with_refresh(|| call_timed("fetch_vehicle_data", self.http_get(&api_url)));
 
-        let reply = TeslaApiClient::handle_result::<Reply<VehicleData>>(result)?;
+        let reply = TeslaApiClient::handle_result::<Reply<VehicleData>>(result)
+            .with_context(|| format!("Failed to fetch vehicle data for vehicle {}", vehicle_id))?;
         Ok(reply.response)
     }
 
-    fn handle_result<T: DeserializeOwned>(result: Result<Response, Error>) -> Result<T> {
-        match result {
-            Err(Status(401, _)) => {
-                return Err(TeslaApiError::LoginFailure.into());
-            }
-            Err(Status(444, response)) => {
-                let text: String = response.into_string()?;
-                return Err(TeslaApiError::Blocked(text).into());
-            }
-            Err(Status(_, response)) => {
-                let text: String = response.into_string()?;
-                let error_reply: ErrorReply = serde_json::from_str(&text)?;
-                return Err(TeslaApiError::from(error_reply).into());
-            }
-            Err(Error::Transport(_)) => {
-                return Err(TeslaApiError::Unknown.into());
+    /// Fetches the recent charging sessions of a vehicle.
+    ///
+    /// Returns the same errors as [`TeslaApiClient::fetch_vehicle`].
+    pub fn fetch_charge_history(&self, vehicle_id: &i64) -> anyhow::Result<Vec<ChargeSession>> {
+        let api_url = format!(
+            "{api_url}/api/1/vehicles/{id}/charges",
+            api_url = self.api_url,
+            id = vehicle_id
+        );
+
+        let result = self.call_with_refresh(|| call_timed("fetch_charge_history", self.http_get(&api_url)));
+
+        let reply = TeslaApiClient::handle_result::<Reply<Vec<ChargeSession>>>(result)
+            .with_context(|| format!("Failed to fetch charge history for vehicle {}", vehicle_id))?;
+        Ok(reply.response)
+    }
+
+    /// Fetches the vehicles and energy sites of the account.
+    ///
+    /// Returns the same errors as [`TeslaApiClient::fetch_vehicles`].
+    pub fn fetch_products(&self) -> anyhow::Result<Vec<Product>> {
+        let api_url = format!("{api_url}/api/1/products", api_url = self.api_url);
+        let result = self.call_with_refresh(|| call_timed("fetch_products", self.http_get(&api_url)));
+
+        let reply = TeslaApiClient::handle_result::<Reply<Vec<Product>>>(result)
+            .context("Failed to fetch products")?;
+        Ok(reply.response)
+    }
+
+    /// Fetches the battery, solar and grid power of an energy site.
+    ///
+    /// Returns [`TeslaApiError::UnknownApiError`] for an unknown `site_id`.
+    pub fn fetch_energy_site_status(&self, site_id: &i64) -> anyhow::Result<EnergySiteStatus> {
+        let api_url = format!(
+            "{api_url}/api/1/energy_sites/{id}/site_status",
+            api_url = self.api_url,
+            id = site_id
+        );
+
+        let result = self.call_with_refresh(|| call_timed("fetch_energy_site_status", self.http_get(&api_url)));
+
+        let reply = TeslaApiClient::handle_result::<Reply<EnergySiteStatus>>(result)
+            .with_context(|| format!("Failed to fetch status of energy site {}", site_id))?;
+        Ok(reply.response)
+    }
+
+    fn handle_result<T: DeserializeOwned>(result: CallResult) -> Result<T> {
+        match result.map_err(|err| *err) {
+            Err(Status(code, response)) => {
+                let retry_after = retry::retry_after(&response);
+                let body = response.into_string().context("Failed to read error response");
+                map_response(code, retry_after, body)
             }
+            Err(Error::Transport(_)) => Err(TeslaApiError::Unknown.into()),
             Ok(response) => {
-                let json: String = response.into_string()?;
-                let result: serde_json::error::Result<T> = serde_json::from_str(&json);
-                match result {
-                    Ok(reply) => Ok(reply),
-                    Err(err) => {
-                        Err(TeslaApiError::JsonDeserializationError(format!("{:?}: {}", err, json)).into())
-                    }
-                }
+                let status = response.status();
+                let body = response.into_string().context("Failed to read response");
+                map_response(status, None, body)
             }
         }
     }
 
+    /// Sends a single `wake_up` request and returns the vehicle as reported at that moment,
+    /// which is usually still asleep.
+    ///
+    /// Returns the same errors as [`TeslaApiClient::fetch_vehicle`].
     pub fn wake_vehicle(&self, vehicle_id: &i64) -> anyhow::Result<Vehicle> {
         let api_url = format!(
             "{api_url}/api/1/vehicles/{id}/wake_up",
-            api_url = API_URL,
+            api_url = self.api_url,
             id = vehicle_id
         );
 
-        let result = self.http_post(&api_url).call();
+        let result = self.call_with_refresh(|| call_timed("wake_vehicle", self.http_post(&api_url)));
 
-        let reply = TeslaApiClient::handle_result::<Reply<Vehicle>>(result)?;
+        let reply = TeslaApiClient::handle_result::<Reply<Vehicle>>(result)
+            .with_context(|| format!("Failed to wake vehicle {}", vehicle_id))?;
         Ok(reply.response)
     }
 
-    pub fn wake_vehicle_poll(&self, vehicle_id: &i64) -> anyhow::Result<()> {
+    /// Wakes a vehicle, retrying `wake_up` every 5 seconds for up to 7 attempts.
+    ///
+    /// Returns [`TeslaApiError::WakeTimeout`] if the vehicle is still asleep after the last
+    /// attempt, or the error of the failing `wake_up` request.
+    pub fn wake_vehicle_poll(&self, vehicle_id: &i64) -> anyhow::Result<WakeResult> {
+        self.wake_vehicle_poll_observed(vehicle_id, || {})
+    }
+
+    /// Same as `wake_vehicle_poll`, calling `on_attempt` before every `wake_up` request.
+    pub fn wake_vehicle_poll_observed(&self, vehicle_id: &i64, mut on_attempt: impl FnMut()) -> anyhow::Result<WakeResult> {
+        let started = Instant::now();
+        on_attempt();
         let mut vehicle = self.wake_vehicle(vehicle_id)?;
-        let mut count = 0;
-        while vehicle.is_asleep() && count < 6 {
+        let mut attempts = 1;
+        while vehicle.is_asleep() && attempts < 7 {
             sleep(Duration::from_secs(5));
+            on_attempt();
             vehicle = self.wake_vehicle(vehicle_id)?;
-            count += 1;
+            attempts += 1;
         }
         if vehicle.is_asleep() {
-            return Err(TeslaApiError::WakeTimeout().into());
+            return Err(anyhow::Error::new(TeslaApiError::WakeTimeout())
+                .context(format!("Vehicle {} is still asleep after {} wake attempts", vehicle_id, attempts)));
         }
-        Ok(())
+        Ok(WakeResult { attempts, total_duration: started.elapsed() })
     }
 
-    pub fn fetch_all_vehicles_data(&self) -> anyhow::Result<Vec<VehicleData>> {
+    /// Fetches the data of every vehicle of the account, waking sleeping vehicles first.
+    ///
+    /// Only fails if the vehicle list can't be fetched. Every vehicle gets an entry in the result,
+    /// the vehicles whose data couldn't be fetched with the error, e.g.
+    /// [`TeslaApiError::VehicleUnavailable`] for a vehicle that could not be woken.
+    ///
+    /// ```no_run
+    /// use tesla_api_exporter::tesla_api_client::dtos::AuthToken;
+    /// use tesla_api_exporter::tesla_api_client::TeslaApiClient;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let client = TeslaApiClient::create(AuthToken::from_env())?;
+    /// for result in client.fetch_all_vehicles_data()? {
+    ///     match result {
+    ///         Ok(data) => println!("{}: {} mi", data.display_name, data.charge_state.battery_range),
+    ///         Err((vehicle, err)) => println!("{}: {:#}", vehicle.display_name, err),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fetch_all_vehicles_data(&self) -> anyhow::Result<Vec<VehicleDataResult>> {
         Ok(self
-            .fetch_vehicles()?
+            .fetch_vehicles()
+            .context("Failed to fetch vehicles data")?
             .into_iter()
-            .filter_map(|v| {
+            .map(|v| {
                 if v.is_asleep() {
                     if let Err(e) = self.wake_vehicle_poll(&v.id) {
                         warn!("Failed to wake vehicle {:?}", e)
                     }
                 }
-                self.fetch_vehicle_data(&v.id).ok()
+                self.fetch_vehicle_data(&v.id).map_err(|e| (Box::new(v), e))
             })
-            .collect::<Vec<VehicleData>>())
+            .collect())
+    }
+
+    /// Same as [`TeslaApiClient::fetch_all_vehicles_data`], with the fetched data and the failed
+    /// vehicles separated.
+    pub fn fetch_all_vehicles_data_tolerant(&self) -> anyhow::Result<(Vec<VehicleData>, Vec<FailedVehicle>)> {
+        let (fetched, failed): (Vec<_>, Vec<_>) = self
+            .fetch_all_vehicles_data()?
+            .into_iter()
+            .partition(Result::is_ok);
+
+        Ok((
+            fetched.into_iter().filter_map(Result::ok).collect(),
+            failed.into_iter().filter_map(Result::err).collect(),
+        ))
+    }
+
+    /// Sends the request built by `request`. When the access token is rejected with a 401 the token
+    /// is refreshed and the request replayed once, so an expired token doesn't fail the call.
+    fn call_with_refresh(&self, request: impl Fn() -> CallResult) -> CallResult {
+        match self.call_with_retry(&request) {
+            Err(err) if matches!(*err, Status(401, _)) => {
+                if let Err(refresh_err) = self.refresh_auth() {
+                    warn!("Failed to refresh rejected auth token: {:#}", refresh_err);
+                    return Err(err);
+                }
+                info!("Refreshed rejected auth token, retrying request");
+                self.call_with_retry(&request)
+            }
+            result => result,
+        }
+    }
+
+    /// Sends the request built by `request`, retrying transient failures per the retry policy.
+    fn call_with_retry(&self, request: &impl Fn() -> CallResult) -> CallResult {
+        let policy = match &self.retry_policy {
+            None => return request(),
+            Some(policy) => policy,
+        };
+
+        let mut attempt = 1;
+        loop {
+            let result = request();
+            if attempt >= policy.max_attempts || !RetryPolicy::is_retryable(&result) {
+                return result;
+            }
+            let delay = match policy.retry_delay(attempt, &result) {
+                Some(delay) => delay,
+                None => return result,
+            };
+            warn!("Retrying failed request: Attempt=\"{}\" Waiting=\"{:?}\" error=\"{}\"",
+                  attempt, delay, result.err().map(|err| err.to_string()).unwrap_or_default());
+            sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    /// Refreshes the access token when it is about to expire, so requests don't fail with a 401.
+    /// Failing to refresh is only logged, the request is still sent with the current token.
+    fn refresh_auth_if_expired(&self) {
+        if !self.is_auth_expired() {
+            return;
+        }
+        // Serializes refreshes, other threads find the token refreshed once they get the lock
+        let _refreshing = self.refresh_lock.lock().expect("Auth refresh lock is poisoned");
+        if !self.is_auth_expired() {
+            return;
+        }
+        match self.refresh_auth() {
+            Ok(_) => info!("Refreshed expiring auth token"),
+            Err(err) => warn!("Failed to refresh expiring auth token: {:#}", err),
+        }
     }
 
     fn http_get(&self, url: &String) -> Request {
+        self.refresh_auth_if_expired();
         self.agent.get(url)
-            .set("Authorization", &format!("Bearer {}", &self.auth_token.access_token))
+            .set("Authorization", &format!("Bearer {}", &self.read_auth_token().access_token))
             .set("User-Agent", USER_AGENT)
     }
 
     fn http_post(&self, url: &String) -> Request {
+        self.refresh_auth_if_expired();
         self.agent.post(url)
-            .set("Authorization", &format!("Bearer {}", &self.auth_token.access_token))
+            .set("Authorization", &format!("Bearer {}", &self.read_auth_token().access_token))
             .set("User-Agent", USER_AGENT)
     }
 }
+
+/// Sends `request` and records how long the API took to respond in `tesla_api_call_duration_seconds`.
+fn call_timed(endpoint: &str, request: Request) -> CallResult {
+    let started = Instant::now();
+    let result = request.call().map_err(Box::new);
+    let status = match &result {
+        Ok(response) => response.status().to_string(),
+        Err(err) => match &**err {
+            Status(code, _) => code.to_string(),
+            Error::Transport(_) => "transport".to_string(),
+        },
+    };
+    API_CALL_DURATION_HISTOGRAM
+        .with_label_values(&[endpoint, &status])
+        .observe(started.elapsed().as_secs_f64());
+    result
+}
+
+/// Maps the status and body of a response to the reply or a [`TeslaApiError`], shared by the
+/// blocking and the async client.
+///
+/// The body is only read for replies and for errors that report it, e.g. a 401 without a readable
+/// body is still a [`TeslaApiError::LoginFailure`].
+pub(crate) fn map_response<T: DeserializeOwned>(status: u16, retry_after: Option<Duration>, body: Result<String>) -> Result<T> {
+    match status {
+        401 => Err(TeslaApiError::LoginFailure.into()),
+        429 => Err(TeslaApiError::RateLimited(retry_after.unwrap_or(DEFAULT_RETRY_AFTER)).into()),
+        444 => Err(TeslaApiError::Blocked(body?).into()),
+        400..=599 => {
+            let text = body?;
+            let error_reply: ErrorReply = serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse error response: {}", text))?;
+            Err(TeslaApiError::from(error_reply).into())
+        }
+        _ => {
+            let json = body?;
+            serde_json::from_str(&json)
+                .map_err(|err| TeslaApiError::JsonDeserializationError(format!("{:?}: {}", err, json)).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::thread::JoinHandle;
+    use std::time::SystemTime;
+
+    use super::*;
+
+    /// Answers one request per response body in order, returns the requests' head lines.
+    fn serve(bodies: Vec<&'static str>) -> Result<(String, JoinHandle<Vec<String>>)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let server = thread::spawn(move || {
+            bodies.into_iter().map(|body| {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                reader.read_exact(&mut vec![0; content_length]).unwrap();
+                write!(reader.get_mut(), "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                       body.len(), body).unwrap();
+                head
+            }).collect()
+        });
+        Ok((url, server))
+    }
+
+    fn auth_token(expires_at: Option<SystemTime>) -> AuthToken {
+        AuthToken {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            expires_in: None,
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn should_call_configured_api_url() -> Result<()> {
+        let (api_url, server) = serve(vec![r#"{"response":[],"count":0}"#])?;
+
+        let client = TeslaApiClient::builder(auth_token(None)).api_url(api_url).build()?;
+
+        assert_eq!(client.fetch_vehicles()?.len(), 0);
+        assert!(server.join().unwrap()[0].starts_with("GET /api/1/vehicles HTTP/1.1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_time_api_calls_by_endpoint_and_status() -> Result<()> {
+        let (api_url, server) = serve(vec![r#"{"response":[],"count":0}"#])?;
+        let client = TeslaApiClient::builder(auth_token(None)).api_url(api_url).build()?;
+        let calls = || API_CALL_DURATION_HISTOGRAM.with_label_values(&["fetch_products", "200"]).get_sample_count();
+        let calls_before = calls();
+
+        assert_eq!(client.fetch_products()?.len(), 0);
+        server.join().unwrap();
+        assert_eq!(calls(), calls_before + 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_refresh_expired_token_before_request() -> Result<()> {
+        let (url, server) = serve(vec![
+            r#"{"access_token":"refreshed","refresh_token":"refresh2","expires_in":28800}"#,
+            r#"{"response":[],"count":0}"#,
+        ])?;
+
+        let client = TeslaApiClient::builder(auth_token(Some(SystemTime::now() - Duration::from_secs(60))))
+            .api_url(url.clone())
+            .auth_api_url(url)
+            .build()?;
+
+        assert_eq!(client.fetch_vehicles()?.len(), 0);
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /oauth2/v3/token HTTP/1.1"));
+        assert!(requests[1].starts_with("GET /api/1/vehicles HTTP/1.1"));
+        assert!(requests[1].to_lowercase().contains("authorization: bearer refreshed"));
+        assert!(!client.is_auth_expired());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_return_retry_after_of_rate_limited_requests() -> Result<()> {
+        let rate_limited = |headers: &str| -> Result<TeslaApiError> {
+            let response: Response = format!("HTTP/1.1 429 Too Many Requests\r\n{}\r\n", headers).parse()?;
+            let err = TeslaApiClient::handle_result::<Vehicle>(Err(Box::new(Status(429, response)))).unwrap_err();
+            err.downcast::<TeslaApiError>()
+        };
+
+        assert_eq!(rate_limited("Retry-After: 120\r\n")?, TeslaApiError::RateLimited(Duration::from_secs(120)));
+        assert_eq!(rate_limited("")?, TeslaApiError::RateLimited(DEFAULT_RETRY_AFTER));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_map_response_status_to_api_errors() {
+        let api_error = |status: u16, body: &str| -> TeslaApiError {
+            map_response::<Reply<Vehicle>>(status, None, Ok(body.to_string()))
+                .unwrap_err()
+                .downcast::<TeslaApiError>()
+                .expect("Not a TeslaApiError")
+        };
+
+        assert_eq!(api_error(401, ""), TeslaApiError::LoginFailure);
+        assert_eq!(api_error(444, "blocked"), TeslaApiError::Blocked("blocked".to_string()));
+        assert_eq!(api_error(408, r#"{"error": "vehicle unavailable: {:error=>\"vehicle unavailable:\"}"}"#),
+                   TeslaApiError::VehicleUnavailable());
+        assert!(matches!(api_error(200, "{}"), TeslaApiError::JsonDeserializationError(_)));
+
+        let unreadable = map_response::<Reply<Vehicle>>(401, None, Err(anyhow::anyhow!("Connection reset")));
+        assert_eq!(unreadable.unwrap_err().downcast_ref::<TeslaApiError>(), Some(&TeslaApiError::LoginFailure));
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use ureq::{Error, Error::Status, Response};
+
+use crate::tesla_api_client::CallResult;
+
+/// Status codes worth retrying: vehicle unavailable, rate limited and service unavailable.
+const RETRYABLE_STATUS_CODES: [u16; 3] = [408, 429, 503];
+
+/// How often and how long to wait between retries of a failed request. The delay doubles with
+/// every attempt up to `max_delay`, with `jitter` each delay is randomised to between half and
+/// all of it so vehicles polled in lockstep spread out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts including the first request, `1` never retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Transport errors and 408, 429 and 503 responses are retried, e.g. a 401 or 444 isn't. A
+    /// `Retry-After` longer than `max_delay` isn't waited for, see [`RetryPolicy::retry_delay`].
+    pub fn is_retryable(result: &CallResult) -> bool {
+        match result.as_ref().map_err(|err| &**err) {
+            Err(Status(code, _)) => RETRYABLE_STATUS_CODES.contains(code),
+            Err(Error::Transport(_)) => true,
+            Ok(_) => false,
+        }
+    }
+
+    /// The delay before retrying the failed `result`, the response's `Retry-After` if it has one.
+    /// `None` if the API asked to wait longer than `max_delay`, the caller should back off instead.
+    pub fn retry_delay(&self, attempt: u32, result: &CallResult) -> Option<Duration> {
+        match result.as_ref().map_err(|err| &**err) {
+            Err(Status(_, response)) => match retry_after(response) {
+                Some(retry_after) if retry_after > self.max_delay => None,
+                Some(retry_after) => Some(retry_after),
+                None => Some(self.delay(attempt)),
+            },
+            _ => Some(self.delay(attempt)),
+        }
+    }
+
+    /// The delay before retrying after the `attempt`th request failed, counting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay
+            .checked_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        if !self.jitter {
+            return delay;
+        }
+        delay.mul_f64(0.5 + 0.5 * random_fraction())
+    }
+}
+
+/// The `Retry-After` header of a 429 or 503 response in seconds, HTTP dates aren't supported.
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    parse_retry_after(response.header("Retry-After")?)
+}
+
+/// A `Retry-After` header value in seconds, see [`retry_after`].
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// A random number in `[0, 1)`, from the randomly seeded std hasher to avoid a dependency.
+pub(crate) fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_double_delay_up_to_max_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+            jitter: false,
+        };
+
+        let delays: Vec<Duration> = (1..=4).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(delays, vec![1, 2, 4, 5].into_iter().map(Duration::from_secs).collect::<Vec<_>>());
+        assert_eq!(policy.delay(100), Duration::from_secs(5));
+
+        let jittered = RetryPolicy { jitter: true, ..policy }.delay(3);
+        assert!(jittered >= Duration::from_secs(2) && jittered <= Duration::from_secs(4));
+    }
+
+    #[test]
+    fn should_only_retry_transient_errors() {
+        let status = |code: u16| -> CallResult {
+            Err(Box::new(Status(code, Response::new(code, "", "").unwrap())))
+        };
+
+        assert!(RetryPolicy::is_retryable(&status(408)));
+        assert!(RetryPolicy::is_retryable(&status(429)));
+        assert!(RetryPolicy::is_retryable(&status(503)));
+        assert!(!RetryPolicy::is_retryable(&status(401)));
+        assert!(!RetryPolicy::is_retryable(&status(444)));
+        assert!(!RetryPolicy::is_retryable(&Ok(Response::new(200, "OK", "").unwrap())));
+    }
+
+    #[test]
+    fn should_wait_for_short_retry_after_only() {
+        let policy = RetryPolicy { jitter: false, ..RetryPolicy::default() };
+        let rate_limited = |retry_after: &str| -> CallResult {
+            let response = format!("HTTP/1.1 429 Too Many Requests\r\nRetry-After: {}\r\n\r\n", retry_after);
+            Err(Box::new(Status(429, response.parse::<Response>().unwrap())))
+        };
+
+        assert_eq!(policy.retry_delay(1, &rate_limited("5")), Some(Duration::from_secs(5)));
+        assert_eq!(policy.retry_delay(1, &rate_limited("300")), None);
+        assert_eq!(policy.retry_delay(2, &rate_limited("Wed, 21 Oct 2015 07:28:00 GMT")), Some(Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{bail, Result};
+use ring::digest::{digest, SHA256};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, Error, OwnedTrustAnchor, RootCertStore, ServerName};
+
+/// Verifies the server certificate chain as usual and additionally requires the SHA-256 hash of
+/// the end-entity certificate to match a pinned value.
+pub struct PinnedCertVerifier {
+    pin: Vec<u8>,
+    inner: WebPkiVerifier,
+}
+
+impl PinnedCertVerifier {
+    /// `pin` is the hex encoded SHA-256 hash of the DER certificate, `:` separators are allowed.
+    pub fn new(pin: &str) -> Result<Self> {
+        Ok(PinnedCertVerifier {
+            pin: decode_hex(pin)?,
+            inner: WebPkiVerifier::new(root_cert_store(), None),
+        })
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+
+        if digest(&SHA256, &end_entity.0).as_ref() != self.pin.as_slice() {
+            return Err(Error::General("Server certificate does not match TESLA_CERT_PIN_SHA256".to_string()));
+        }
+        Ok(verified)
+    }
+}
+
+pub fn pinned_tls_config(pin: &str) -> Result<Arc<ClientConfig>> {
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier::new(pin)?))
+        .with_no_client_auth();
+
+    Ok(Arc::new(config))
+}
+
+fn root_cert_store() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+    }));
+    roots
+}
+
+fn decode_hex(pin: &str) -> Result<Vec<u8>> {
+    let hex: String = pin.chars().filter(|c| *c != ':').collect();
+    if hex.len() != 64 || !hex.is_ascii() {
+        bail!("Expected a 64 character SHA-256 hex digest, got \"{}\"", pin);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_decode_pin_with_and_without_separators() -> Result<()> {
+        let plain = "a1".repeat(32);
+        let separated = vec!["A1"; 32].join(":");
+
+        assert_eq!(decode_hex(&plain)?, vec![0xa1; 32]);
+        assert_eq!(decode_hex(&separated)?, vec![0xa1; 32]);
+        assert!(decode_hex("abcd").is_err());
+        assert!(decode_hex(&"zz".repeat(32)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::stop_signal::StopSignal;
+use crate::tesla_api_client::TeslaApiClient;
+
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10 * 60);
+
+/// Refreshes the access token once 80% of its lifetime has passed, so requests never see an
+/// expired token. Tokens with an unknown expiry, e.g. read from the environment, are refreshed
+/// right away to learn it.
+///
+/// The client shares its token with every clone, so all pollers pick up the refreshed token.
+pub struct TokenRefreshScheduler {
+    client: TeslaApiClient,
+}
+
+impl TokenRefreshScheduler {
+    pub fn new(client: TeslaApiClient) -> Self {
+        TokenRefreshScheduler { client }
+    }
+
+    /// Refreshes on schedule until `stop` is set, retrying failed refreshes with exponential backoff.
+    pub fn spawn(self, stop: StopSignal) -> JoinHandle<()> {
+        thread::spawn(move || {
+            let mut retry_delay = MIN_RETRY_DELAY;
+            loop {
+                let refresh_due_in = self.client.auth_token().read().expect("Auth token lock is poisoned").refresh_due_in();
+                if !stop.sleep(refresh_due_in) {
+                    return;
+                }
+
+                match self.client.refresh_auth() {
+                    Ok(_) => {
+                        info!("Refreshed auth token: Next=\"{:?}\"",
+                              self.client.auth_token().read().expect("Auth token lock is poisoned").refresh_due_in());
+                        retry_delay = MIN_RETRY_DELAY;
+                    }
+                    Err(err) => {
+                        warn!("Failed to refresh auth token: Waiting=\"{:?}\" error=\"{:#}\"", retry_delay, err);
+                        if !stop.sleep(retry_delay) {
+                            return;
+                        }
+                        retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                    }
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,94 @@
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::tesla_api_client::dtos::AuthToken;
+
+/// Where refreshed auth tokens are kept, so a restart doesn't need new tokens.
+pub trait TokenStore: Debug + Send + Sync {
+    fn load(&self) -> Result<AuthToken>;
+    fn save(&self, auth_token: &AuthToken) -> Result<()>;
+}
+
+/// Keeps the auth token in a JSON file.
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileTokenStore { path: path.into() }
+    }
+
+    /// `None` unless `TESLA_TOKEN_FILE` is set.
+    pub fn from_env() -> Option<Self> {
+        env::var("TESLA_TOKEN_FILE").ok().map(FileTokenStore::new)
+    }
+
+    /// The token file of the `account_index`th account when several accounts are polled, e.g.
+    /// `token.2.json` for `token.json`. The first account keeps this file.
+    pub fn for_account(&self, account_index: usize) -> Self {
+        if account_index <= 1 {
+            return self.clone();
+        }
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match self.path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, account_index, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, account_index),
+        };
+        FileTokenStore::new(self.path.with_file_name(file_name))
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> Result<AuthToken> {
+        let json = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read token file {}", self.path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("Failed to parse token file {}", self.path.display()))
+    }
+
+    /// Writes a temporary file next to the token file and renames it, so a crash can't leave a
+    /// partially written token behind.
+    fn save(&self, auth_token: &AuthToken) -> Result<()> {
+        let json = serde_json::to_string_pretty(auth_token)?;
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, json).with_context(|| format!("Failed to write token file {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Failed to replace token file {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn should_save_and_load_auth_token() -> Result<()> {
+        let path = env::temp_dir().join(format!("tesla-api-exporter-token-{}.json", process::id()));
+        let store = FileTokenStore::new(&path);
+        assert!(store.load().is_err());
+
+        store.save(&AuthToken {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            expires_in: Some(28800),
+            expires_at: None,
+        }.with_expiry_from_now())?;
+        let auth_token = store.load()?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(auth_token.access_token, "access");
+        assert_eq!(auth_token.refresh_token, "refresh");
+        assert!(!auth_token.is_expired());
+        assert_eq!(FileTokenStore::new("/tmp/token.json").for_account(2).path, PathBuf::from("/tmp/token.2.json"));
+        assert_eq!(FileTokenStore::new("/tmp/token.json").for_account(1).path, PathBuf::from("/tmp/token.json"));
+
+        Ok(())
+    }
+}
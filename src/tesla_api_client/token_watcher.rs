@@ -0,0 +1,119 @@
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::info;
+
+use crate::stop_signal::StopSignal;
+use crate::tesla_api_client::dtos::AuthToken;
+use crate::tesla_api_client::token_store::TokenStore;
+
+/// Picks up a token written to the token store by another process without restarting, for setups
+/// where a sidecar (e.g. Vault Agent) rotates the token in `TESLA_TOKEN_FILE`.
+pub struct TokenWatcher {
+    auth_token: Arc<RwLock<AuthToken>>,
+    token_store: Arc<dyn TokenStore>,
+    interval: Duration,
+    /// The access token last read from the store, the shared token is only replaced when it changes.
+    last_seen: Option<String>,
+}
+
+impl TokenWatcher {
+    pub fn new(auth_token: Arc<RwLock<AuthToken>>, token_store: Arc<dyn TokenStore>, interval: Duration) -> Self {
+        let last_seen = token_store.load().ok().map(|stored| stored.access_token);
+        TokenWatcher { auth_token, token_store, interval, last_seen }
+    }
+
+    /// Re-reads the token store every `interval` until `stop` is set.
+    pub fn spawn(mut self, stop: StopSignal) -> JoinHandle<()> {
+        thread::spawn(move || {
+            while stop.sleep(self.interval) {
+                self.reload();
+            }
+        })
+    }
+
+    /// Replaces the shared token when the stored access token changed since the last read, returns
+    /// whether it did. Tokens the client saved itself after a refresh are already in use and kept.
+    pub fn reload(&mut self) -> bool {
+        let stored = match self.token_store.load() {
+            Ok(stored) if !stored.access_token.is_empty() => stored,
+            _ => return false,
+        };
+        if self.last_seen.as_ref() == Some(&stored.access_token) {
+            return false;
+        }
+        self.last_seen = Some(stored.access_token.clone());
+
+        let mut auth_token = self.auth_token.write().expect("Auth token lock is poisoned");
+        if auth_token.access_token == stored.access_token {
+            return false;
+        }
+
+        info!("Detected new access token in the token store, replacing auth token");
+        *auth_token = stored;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct MemoryTokenStore(Mutex<AuthToken>);
+
+    impl TokenStore for MemoryTokenStore {
+        fn load(&self) -> Result<AuthToken> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        fn save(&self, auth_token: &AuthToken) -> Result<()> {
+            *self.0.lock().unwrap() = auth_token.clone();
+            Ok(())
+        }
+    }
+
+    fn auth_token(access_token: &str, refresh_token: &str) -> AuthToken {
+        AuthToken {
+            access_token: access_token.to_string(),
+            refresh_token: refresh_token.to_string(),
+            expires_in: None,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn should_reload_changed_stored_token_only() -> Result<()> {
+        let shared = Arc::new(RwLock::new(auth_token("old", "refresh")));
+        let store = Arc::new(MemoryTokenStore(Mutex::new(auth_token("old", "refresh"))));
+        let mut watcher = TokenWatcher::new(shared.clone(), store.clone(), Duration::from_secs(60));
+        assert!(!watcher.reload());
+
+        // Refreshed by the client: the store holds the token already in use
+        let refreshed = auth_token("refreshed", "refresh2").with_expiry_from_now();
+        *shared.write().unwrap() = refreshed.clone();
+        store.save(&refreshed)?;
+        assert!(!watcher.reload());
+        assert_eq!(shared.read().unwrap().expires_at, refreshed.expires_at);
+
+        // Rotated by a sidecar
+        store.save(&auth_token("rotated", "refresh3"))?;
+        assert!(watcher.reload());
+        assert!(!watcher.reload());
+        assert_eq!(shared.read().unwrap().access_token, "rotated");
+        assert_eq!(shared.read().unwrap().refresh_token, "refresh3");
+
+        // The shared token changing on its own doesn't bring the stored one back
+        *shared.write().unwrap() = auth_token("refreshed again", "refresh4");
+        assert!(!watcher.reload());
+        assert_eq!(shared.read().unwrap().access_token, "refreshed again");
+
+        Ok(())
+    }
+}
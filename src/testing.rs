@@ -0,0 +1,128 @@
+//! Test doubles for code using the Tesla API, enabled with the `testing` feature.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::tesla_api_client::dtos::{TeslaApiError, Vehicle, VehicleData};
+use crate::tesla_api_client::{TeslaApiClientTrait, VehicleDataResult, WakeResult};
+
+#[derive(Debug, Default)]
+struct FakeResponses {
+    vehicles: Vec<Vehicle>,
+    vehicle: VecDeque<Vehicle>,
+    vehicle_data: VecDeque<Result<VehicleData, TeslaApiError>>,
+    wake_results: VecDeque<Result<WakeResult, TeslaApiError>>,
+    calls: Vec<&'static str>,
+}
+
+/// A [`TeslaApiClientTrait`] replying with canned responses and recording the calls made.
+///
+/// `fetch_vehicles` returns the vehicles set with [`FakeTeslaApiClient::set_vehicles`] on every
+/// call, the other requests reply with the next pushed response in order and fail once there is
+/// none. Waking succeeds after one attempt unless a wake result was pushed.
+///
+/// Clones share the responses and calls, so a clone can be handed to a polling thread.
+#[derive(Debug, Clone, Default)]
+pub struct FakeTeslaApiClient {
+    responses: Arc<Mutex<FakeResponses>>,
+}
+
+impl FakeTeslaApiClient {
+    pub fn new() -> Self {
+        FakeTeslaApiClient::default()
+    }
+
+    pub fn set_vehicles(&self, vehicles: Vec<Vehicle>) {
+        self.lock().vehicles = vehicles;
+    }
+
+    /// The next reply of `fetch_vehicle`.
+    pub fn push_vehicle(&self, vehicle: Vehicle) {
+        self.lock().vehicle.push_back(vehicle);
+    }
+
+    /// The next reply of `fetch_vehicle_data`.
+    pub fn push_vehicle_data(&self, vehicle_data: VehicleData) {
+        self.lock().vehicle_data.push_back(Ok(vehicle_data));
+    }
+
+    /// The next `fetch_vehicle_data` fails with `err`, e.g. [`TeslaApiError::VehicleUnavailable`].
+    pub fn push_vehicle_data_error(&self, err: TeslaApiError) {
+        self.lock().vehicle_data.push_back(Err(err));
+    }
+
+    /// The next reply of `wake_vehicle_poll`.
+    pub fn push_wake_result(&self, wake_result: Result<WakeResult, TeslaApiError>) {
+        self.lock().wake_results.push_back(wake_result);
+    }
+
+    /// The names of the methods called since the last call, e.g. `["fetch_vehicle", "fetch_vehicle_data"]`.
+    pub fn take_calls(&self) -> Vec<&'static str> {
+        self.lock().calls.drain(..).collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FakeResponses> {
+        self.responses.lock().expect("Fake responses lock is poisoned")
+    }
+
+    fn record(&self, call: &'static str) -> MutexGuard<'_, FakeResponses> {
+        let mut responses = self.lock();
+        responses.calls.push(call);
+        responses
+    }
+}
+
+impl TeslaApiClientTrait for FakeTeslaApiClient {
+    fn fetch_vehicle(&self, _vehicle_id: &i64) -> Result<Vehicle> {
+        self.record("fetch_vehicle").vehicle.pop_front().ok_or_else(|| anyhow!("No vehicle pushed"))
+    }
+
+    fn fetch_vehicles(&self) -> Result<Vec<Vehicle>> {
+        Ok(self.record("fetch_vehicles").vehicles.clone())
+    }
+
+    fn fetch_vehicle_data(&self, _vehicle_id: &i64) -> Result<VehicleData> {
+        match self.record("fetch_vehicle_data").vehicle_data.pop_front() {
+            Some(vehicle_data) => Ok(vehicle_data?),
+            None => Err(anyhow!("No vehicle data pushed")),
+        }
+    }
+
+    fn fetch_all_vehicles_data(&self) -> Result<Vec<VehicleDataResult>> {
+        let vehicles = self.record("fetch_all_vehicles_data").vehicles.clone();
+        Ok(vehicles
+            .into_iter()
+            .map(|vehicle| {
+                let vehicle_data = self.lock().vehicle_data.pop_front();
+                match vehicle_data {
+                    Some(Ok(vehicle_data)) => Ok(vehicle_data),
+                    Some(Err(err)) => Err((Box::new(vehicle), err.into())),
+                    None => Err((Box::new(vehicle), anyhow!("No vehicle data pushed"))),
+                }
+            })
+            .collect())
+    }
+
+    fn wake_vehicle(&self, vehicle_id: &i64) -> Result<Vehicle> {
+        let responses = self.record("wake_vehicle");
+        let vehicle = responses.vehicles.iter().find(|vehicle| vehicle.id == *vehicle_id);
+        vehicle.cloned().ok_or_else(|| anyhow!("Unknown vehicle {}", vehicle_id))
+    }
+
+    fn wake_vehicle_poll_observed(&self, _vehicle_id: &i64, on_attempt: &mut dyn FnMut()) -> Result<WakeResult> {
+        let wake_result = self.record("wake_vehicle_poll").wake_results.pop_front();
+        on_attempt();
+        match wake_result {
+            Some(wake_result) => Ok(wake_result?),
+            None => Ok(WakeResult { attempts: 1, total_duration: Duration::from_secs(1) }),
+        }
+    }
+
+    fn refresh_auth(&self) -> Result<()> {
+        self.lock().calls.push("refresh_auth");
+        Ok(())
+    }
+}
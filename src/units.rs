@@ -0,0 +1,155 @@
+use std::env;
+
+use log::warn;
+use once_cell::sync::Lazy;
+
+const KM_PER_MILE: f64 = 1.60934;
+
+/// The units the gauges are exported in, read from `TESLA_DISTANCE_UNITS` and `TESLA_TEMP_UNITS`.
+pub static UNITS: Lazy<Units> = Lazy::new(Units::from_env);
+
+/// Distance unit of the range, odometer and speed gauges.
+///
+/// The API reports distances in miles whatever the vehicle's `gui_distance_units`, so converting
+/// is a pure post-process.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DistanceUnit {
+    Miles,
+    Kilometers,
+}
+
+impl DistanceUnit {
+    /// The `distance_unit` label of `tesla_config_info`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            DistanceUnit::Miles => "mi",
+            DistanceUnit::Kilometers => "km",
+        }
+    }
+
+    /// For metric help strings, e.g. `Battery Range (Miles)`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DistanceUnit::Miles => "Miles",
+            DistanceUnit::Kilometers => "Kilometers",
+        }
+    }
+
+    pub fn speed_name(&self) -> &'static str {
+        match self {
+            DistanceUnit::Miles => "MPH",
+            DistanceUnit::Kilometers => "km/h",
+        }
+    }
+
+    pub fn from_miles(&self, miles: f64) -> f64 {
+        match self {
+            DistanceUnit::Miles => miles,
+            DistanceUnit::Kilometers => miles * KM_PER_MILE,
+        }
+    }
+}
+
+/// Temperature unit of the climate gauges.
+///
+/// The API reports temperatures in Celsius whatever the vehicle's `gui_temperature_units`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    /// The `temperature_unit` label of `tesla_config_info`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "C",
+            TemperatureUnit::Fahrenheit => "F",
+        }
+    }
+
+    /// For metric help strings, e.g. `Inside Temperature (DegC)`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "DegC",
+            TemperatureUnit::Fahrenheit => "DegF",
+        }
+    }
+
+    pub fn from_celsius(&self, celsius: f64) -> f64 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Units {
+    pub distance: DistanceUnit,
+    pub temperature: TemperatureUnit,
+}
+
+impl Units {
+    /// Reads `TESLA_DISTANCE_UNITS` (`mi` or `km`) and `TESLA_TEMP_UNITS` (`c` or `f`), invalid values
+    /// keep the default of miles and Celsius.
+    pub fn from_env() -> Self {
+        Units::from_lookup(|name| env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = Units::default();
+        let distance = match lookup("TESLA_DISTANCE_UNITS") {
+            None => defaults.distance,
+            Some(value) => match value.trim().to_lowercase().as_str() {
+                "mi" => DistanceUnit::Miles,
+                "km" => DistanceUnit::Kilometers,
+                _ => {
+                    warn!("Ignoring invalid TESLA_DISTANCE_UNITS=\"{}\", using {}", value, defaults.distance.label());
+                    defaults.distance
+                }
+            },
+        };
+        let temperature = match lookup("TESLA_TEMP_UNITS") {
+            None => defaults.temperature,
+            Some(value) => match value.trim().to_lowercase().as_str() {
+                "c" => TemperatureUnit::Celsius,
+                "f" => TemperatureUnit::Fahrenheit,
+                _ => {
+                    warn!("Ignoring invalid TESLA_TEMP_UNITS=\"{}\", using {}", value, defaults.temperature.label());
+                    defaults.temperature
+                }
+            },
+        };
+        Units { distance, temperature }
+    }
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Units { distance: DistanceUnit::Miles, temperature: TemperatureUnit::Celsius }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_convert_to_configured_units() {
+        let lookup = |distance: &'static str, temperature: &'static str| move |name: &str| match name {
+            "TESLA_DISTANCE_UNITS" => Some(distance.to_string()),
+            "TESLA_TEMP_UNITS" => Some(temperature.to_string()),
+            _ => None,
+        };
+
+        let units = Units::from_lookup(lookup("KM", "f"));
+        assert_eq!(units, Units { distance: DistanceUnit::Kilometers, temperature: TemperatureUnit::Fahrenheit });
+        assert_eq!(units.distance.from_miles(100.0), 160.934);
+        assert_eq!(units.temperature.from_celsius(20.0), 68.0);
+
+        assert_eq!(Units::from_lookup(lookup("furlongs", "kelvin")), Units::default());
+        assert_eq!(Units::default().distance.from_miles(100.0), 100.0);
+        assert_eq!(Units::default().temperature.from_celsius(20.0), 20.0);
+    }
+}
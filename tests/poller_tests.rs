@@ -0,0 +1,29 @@
+use anyhow::Result;
+
+use tesla_api_exporter::poller::{fetch_and_record, register};
+use tesla_api_exporter::testing::FakeTeslaApiClient;
+
+fn gauge_value(prometheus: &rocket_prometheus::PrometheusMetrics, name: &str) -> Option<f64> {
+    prometheus.registry()
+        .gather()
+        .iter()
+        .find(|family| family.get_name() == name)
+        .map(|family| family.get_metric()[0].get_gauge().get_value())
+}
+
+#[test]
+fn should_fetch_and_record_vehicle_data_once() -> Result<()> {
+    let prometheus = register();
+    let client = FakeTeslaApiClient::new();
+    client.push_vehicle_data(serde_json::from_str(include_str!("fixtures/vehicle_data.json"))?);
+
+    let car_state = fetch_and_record(&client, 41614331478102467)?;
+
+    assert!(car_state.is_parked());
+    assert_eq!(gauge_value(&prometheus, "tesla_charge_state_battery_level"), Some(87.0));
+    assert_eq!(gauge_value(&prometheus, "tesla_vehicle_state_odometer"), Some(7469.486058));
+    assert_eq!(gauge_value(&prometheus, "tesla_car_state"), Some(1.0));
+    assert_eq!(client.take_calls(), vec!["fetch_vehicle_data"]);
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use wiremock::matchers::{body_partial_json, header_exists, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use tesla_api_exporter::tesla_api_client::dtos::AuthToken;
+use tesla_api_exporter::tesla_api_client::TeslaApiClient;
+
+/// The vehicle of the fixture, `41614331478102467`.
+pub const VEHICLE_ID: i64 = 41614331478102467;
+
+/// A stand-in for the Owner API and the auth API serving a single vehicle, the one of
+/// `tests/fixtures/vehicle_data.json`.
+pub struct MockTeslaApiServer {
+    server: MockServer,
+}
+
+impl MockTeslaApiServer {
+    /// An account whose vehicle is online.
+    pub async fn start() -> Self {
+        MockTeslaApiServer::start_with_vehicle_state("online").await
+    }
+
+    /// An account whose vehicle is asleep: its data is unavailable until it's woken, and `wake_up`
+    /// reports it online.
+    pub async fn start_asleep() -> Self {
+        MockTeslaApiServer::start_with_vehicle_state("asleep").await
+    }
+
+    async fn start_with_vehicle_state(state: &str) -> Self {
+        let server = MockServer::start().await;
+        let vehicle = vehicle(state);
+
+        Mock::given(method("GET"))
+            .and(path("/api/1/vehicles"))
+            .and(header_exists("Authorization"))
+            .respond_with(reply(json!([vehicle])))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(format!("/api/1/vehicles/{}", VEHICLE_ID)))
+            .and(header_exists("Authorization"))
+            .respond_with(reply(vehicle.clone()))
+            .mount(&server)
+            .await;
+
+        let vehicle_data = if state == "online" {
+            reply(self::vehicle_data())
+        } else {
+            ResponseTemplate::new(408)
+                .set_body_json(json!({"error": "vehicle unavailable: {:error=>\"vehicle unavailable:\"}"}))
+        };
+        Mock::given(method("GET"))
+            .and(path(format!("/api/1/vehicles/{}/vehicle_data", VEHICLE_ID)))
+            .and(header_exists("Authorization"))
+            .respond_with(vehicle_data)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path(format!("/api/1/vehicles/{}/wake_up", VEHICLE_ID)))
+            .and(header_exists("Authorization"))
+            .respond_with(reply(self::vehicle("online")))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/oauth2/v3/token"))
+            .and(body_partial_json(json!({"grant_type": "refresh_token", "refresh_token": "refresh-token"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "refreshed-access-token",
+                "refresh_token": "refreshed-refresh-token",
+                "expires_in": 28800,
+                "token_type": "Bearer",
+            })))
+            .mount(&server)
+            .await;
+
+        MockTeslaApiServer { server }
+    }
+
+    /// The base URL of both APIs.
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// A client of both APIs authenticating with `access-token` / `refresh-token`.
+    pub fn client(&self) -> Result<TeslaApiClient> {
+        let auth_token = AuthToken {
+            access_token: "access-token".to_string(),
+            refresh_token: "refresh-token".to_string(),
+            expires_in: None,
+            expires_at: None,
+        };
+        TeslaApiClient::builder(auth_token)
+            .api_url(self.uri())
+            .auth_api_url(self.uri())
+            .build()
+    }
+}
+
+fn reply(response: Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "response": response }))
+}
+
+fn vehicle_data() -> Value {
+    serde_json::from_str(include_str!("../fixtures/vehicle_data.json")).expect("Invalid vehicle data fixture")
+}
+
+/// The vehicle of the fixture as listed by `/api/1/vehicles`, without its states.
+fn vehicle(state: &str) -> Value {
+    let mut vehicle = vehicle_data();
+    let fields = vehicle.as_object_mut().expect("Vehicle data fixture isn't an object");
+    fields.retain(|_, value| !value.is_object());
+    fields.insert("state".to_string(), json!(state));
+    vehicle
+}
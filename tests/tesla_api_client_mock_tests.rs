@@ -0,0 +1,105 @@
+//! The tests of `tesla_api_client_integration_tests.rs` against a `MockTeslaApiServer`, so they run
+//! without Tesla credentials or a vehicle.
+
+use anyhow::Result;
+
+use tesla_api_exporter::tesla_api_client::dtos::TeslaApiError;
+
+use support::mock_server::{MockTeslaApiServer, VEHICLE_ID};
+
+mod support;
+
+#[tokio::test]
+async fn should_authenticate_and_refresh_authentication() -> Result<()> {
+    let server = MockTeslaApiServer::start().await;
+    let client = server.client()?;
+
+    let refresh_result = client.refresh_auth();
+
+    assert!(refresh_result.is_ok());
+    assert_eq!(client.auth_token().read().unwrap().access_token, "refreshed-access-token");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn should_fetch_vehicles() -> Result<()> {
+    let server = MockTeslaApiServer::start().await;
+    let client = server.client()?;
+
+    let vehicles = client.fetch_vehicles()?;
+
+    assert_eq!(vehicles.len(), 1);
+    assert_eq!(vehicles[0].id, VEHICLE_ID);
+    assert_eq!(vehicles[0].display_name, "Bellwood Auto");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn should_fetch_vehicle_data() -> Result<()> {
+    let server = MockTeslaApiServer::start().await;
+    let client = server.client()?;
+
+    let vehicle_data = client.fetch_vehicle_data(&VEHICLE_ID)?;
+
+    assert_eq!(vehicle_data.state, "online");
+    assert_eq!(vehicle_data.charge_state.battery_level, 87);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn should_fail_to_fetch_vehicle_data_bc_vehicle_is_unavailable() -> Result<()> {
+    let server = MockTeslaApiServer::start_asleep().await;
+    let client = server.client()?;
+
+    let vehicle = client.fetch_vehicle(&VEHICLE_ID)?;
+    let vehicle_data_result = client.fetch_vehicle_data(&vehicle.id);
+
+    assert!(vehicle.is_asleep());
+    assert_eq!(
+        vehicle_data_result.unwrap_err().downcast_ref::<TeslaApiError>(),
+        Some(&TeslaApiError::VehicleUnavailable())
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn should_wake_up_the_vehicle() -> Result<()> {
+    let server = MockTeslaApiServer::start_asleep().await;
+    let client = server.client()?;
+
+    let wake_result = client.wake_vehicle_poll(&VEHICLE_ID)?;
+
+    assert_eq!(wake_result.attempts, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn should_fetch_all_vehicle_data() -> Result<()> {
+    let server = MockTeslaApiServer::start().await;
+    let client = server.client()?;
+
+    let vehicles_data = client.fetch_all_vehicles_data()?;
+
+    assert_eq!(vehicles_data.len(), 1);
+    assert_eq!(vehicles_data[0].as_ref().map(|data| data.id).ok(), Some(VEHICLE_ID));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn should_fetch_all_vehicle_data_tolerant() -> Result<()> {
+    let server = MockTeslaApiServer::start_asleep().await;
+    let client = server.client()?;
+
+    let vehicles = client.fetch_vehicles()?;
+    let (vehicles_data, failed) = client.fetch_all_vehicles_data_tolerant()?;
+
+    assert_eq!(vehicles_data.len() + failed.len(), vehicles.len());
+
+    Ok(())
+}